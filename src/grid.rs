@@ -0,0 +1,317 @@
+use std::collections::HashMap;
+use std::ops::{Index, IndexMut};
+
+// Offsets to the north, south, west and east neighbors, with y increasing downwards
+pub const DIRECTIONS_4: [(i64, i64); 4] = [(0, -1), (0, 1), (-1, 0), (1, 0)];
+
+// As above plus the diagonals
+pub const DIRECTIONS_8: [(i64, i64); 8] = [
+    (0, -1),
+    (0, 1),
+    (-1, 0),
+    (1, 0),
+    (-1, -1),
+    (1, -1),
+    (-1, 1),
+    (1, 1),
+];
+
+// A fixed size grid stored row by row
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Grid<T> {
+    width: usize,
+    height: usize,
+    cells: Vec<T>,
+}
+
+impl<T: Clone> Grid<T> {
+    pub fn new(width: usize, height: usize, fill: T) -> Self {
+        assert!(width > 0, "ERROR: grids must be at least one cell wide");
+        Grid {
+            width,
+            height,
+            cells: vec![fill; width * height],
+        }
+    }
+}
+
+impl<T> Grid<T> {
+    pub fn from_cells(width: usize, cells: Vec<T>) -> Self {
+        assert!(width > 0, "ERROR: grids must be at least one cell wide");
+        assert_eq!(
+            cells.len() % width,
+            0,
+            "ERROR: cells don't fill the last row"
+        );
+        Grid {
+            width,
+            height: cells.len() / width,
+            cells,
+        }
+    }
+
+    // Parse a character map such as the puzzle inputs. Lines shorter than the longest one are
+    // padded with whatever ' ' parses as.
+    pub fn parse(input: &str, mut parse_cell: impl FnMut(char) -> T) -> Self {
+        let lines = input.lines().collect::<Vec<_>>();
+        let width = lines
+            .iter()
+            .map(|line| line.chars().count())
+            .max()
+            .unwrap_or(0);
+        assert!(width > 0, "ERROR: grids must be at least one cell wide");
+        let mut cells = Vec::with_capacity(width * lines.len());
+        for line in lines.iter() {
+            let padding = width - line.chars().count();
+            for ch in line.chars().chain(std::iter::repeat_n(' ', padding)) {
+                cells.push(parse_cell(ch));
+            }
+        }
+        Grid {
+            width,
+            height: lines.len(),
+            cells,
+        }
+    }
+
+    pub fn map<U>(&self, f: impl FnMut(&T) -> U) -> Grid<U> {
+        Grid {
+            width: self.width,
+            height: self.height,
+            cells: self.cells.iter().map(f).collect(),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get(&self, (x, y): (usize, usize)) -> Option<&T> {
+        if x < self.width && y < self.height {
+            Some(&self.cells[y * self.width + x])
+        } else {
+            None
+        }
+    }
+
+    pub fn get_mut(&mut self, (x, y): (usize, usize)) -> Option<&mut T> {
+        if x < self.width && y < self.height {
+            Some(&mut self.cells[y * self.width + x])
+        } else {
+            None
+        }
+    }
+
+    pub fn row(&self, y: usize) -> &[T] {
+        &self.cells[y * self.width..(y + 1) * self.width]
+    }
+
+    // The point `delta` away from `point`, if it is on the grid
+    pub fn offset(&self, (x, y): (usize, usize), (dx, dy): (i64, i64)) -> Option<(usize, usize)> {
+        let x = x as i64 + dx;
+        let y = y as i64 + dy;
+        if x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height {
+            Some((x as usize, y as usize))
+        } else {
+            None
+        }
+    }
+
+    pub fn neighbors4(&self, point: (usize, usize)) -> impl Iterator<Item = (usize, usize)> + '_ {
+        DIRECTIONS_4
+            .iter()
+            .filter_map(move |&delta| self.offset(point, delta))
+    }
+
+    // No 2019 puzzle has a map where diagonal cells touch, but the grids are meant for any map
+    #[allow(dead_code)]
+    pub fn neighbors8(&self, point: (usize, usize)) -> impl Iterator<Item = (usize, usize)> + '_ {
+        DIRECTIONS_8
+            .iter()
+            .filter_map(move |&delta| self.offset(point, delta))
+    }
+
+    pub fn points(&self) -> impl Iterator<Item = (usize, usize)> {
+        let width = self.width;
+        (0..self.height).flat_map(move |y| (0..width).map(move |x| (x, y)))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = ((usize, usize), &T)> {
+        self.points().zip(self.cells.iter())
+    }
+
+    pub fn position(&self, mut predicate: impl FnMut(&T) -> bool) -> Option<(usize, usize)> {
+        self.iter()
+            .find(|(_, cell)| predicate(cell))
+            .map(|(point, _)| point)
+    }
+
+    // One line per row, each ending in a newline
+    pub fn render(&self, mut render_cell: impl FnMut((usize, usize), &T) -> char) -> String {
+        let mut out = String::with_capacity((self.width + 1) * self.height);
+        for (point, cell) in self.iter() {
+            out.push(render_cell(point, cell));
+            if point.0 == self.width - 1 {
+                out.push('\n');
+            }
+        }
+        out
+    }
+}
+
+impl<T> Index<(usize, usize)> for Grid<T> {
+    type Output = T;
+
+    fn index(&self, point: (usize, usize)) -> &T {
+        self.get(point)
+            .unwrap_or_else(|| panic!("ERROR: {:?} is outside the grid", point))
+    }
+}
+
+impl<T> IndexMut<(usize, usize)> for Grid<T> {
+    fn index_mut(&mut self, point: (usize, usize)) -> &mut T {
+        self.get_mut(point)
+            .unwrap_or_else(|| panic!("ERROR: {:?} is outside the grid", point))
+    }
+}
+
+// A grid without bounds, for when things wander off in any direction from where they start
+#[derive(Clone, Debug, PartialEq)]
+pub struct SparseGrid<T> {
+    cells: HashMap<(i64, i64), T>,
+}
+
+impl<T> Default for SparseGrid<T> {
+    fn default() -> Self {
+        SparseGrid {
+            cells: HashMap::new(),
+        }
+    }
+}
+
+impl<T> SparseGrid<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, point: (i64, i64)) -> Option<&T> {
+        self.cells.get(&point)
+    }
+
+    pub fn contains(&self, point: (i64, i64)) -> bool {
+        self.cells.contains_key(&point)
+    }
+
+    pub fn insert(&mut self, point: (i64, i64), cell: T) -> Option<T> {
+        self.cells.insert(point, cell)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = ((i64, i64), &T)> {
+        self.cells.iter().map(|(point, cell)| (*point, cell))
+    }
+
+    pub fn neighbors4((x, y): (i64, i64)) -> impl Iterator<Item = (i64, i64)> {
+        DIRECTIONS_4.iter().map(move |(dx, dy)| (x + dx, y + dy))
+    }
+
+    // See Grid::neighbors8
+    #[allow(dead_code)]
+    pub fn neighbors8((x, y): (i64, i64)) -> impl Iterator<Item = (i64, i64)> {
+        DIRECTIONS_8.iter().map(move |(dx, dy)| (x + dx, y + dy))
+    }
+
+    // The top left and bottom right corners of the smallest rectangle holding every cell
+    pub fn bounds(&self) -> Option<((i64, i64), (i64, i64))> {
+        let mut points = self.cells.keys();
+        let &first = points.next()?;
+        Some(points.fold(
+            (first, first),
+            |((min_x, min_y), (max_x, max_y)), &(x, y)| {
+                ((min_x.min(x), min_y.min(y)), (max_x.max(x), max_y.max(y)))
+            },
+        ))
+    }
+
+    // Render the cells within the bounds, using `empty` for the points without one
+    pub fn render(
+        &self,
+        empty: char,
+        mut render_cell: impl FnMut((i64, i64), &T) -> char,
+    ) -> String {
+        let ((min_x, min_y), (max_x, max_y)) = match self.bounds() {
+            Some(bounds) => bounds,
+            None => return String::new(),
+        };
+        let mut out = String::new();
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                out.push(
+                    self.get((x, y))
+                        .map_or(empty, |cell| render_cell((x, y), cell)),
+                );
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_render() {
+        let grid = Grid::parse("#.#\n..\n", |ch| ch == '#');
+        assert_eq!((grid.width(), grid.height()), (3, 2));
+        assert_eq!(grid[(2, 0)], true);
+        assert_eq!(grid.get((3, 0)), None);
+        assert_eq!(grid.position(|&wall| !wall), Some((1, 0)));
+        let rendered = grid.render(|_, &wall| if wall { '#' } else { '.' });
+        assert_eq!(rendered, "#.#\n...\n");
+    }
+
+    #[test]
+    fn neighbors() {
+        let grid = Grid::new(3, 3, 0);
+        assert_eq!(
+            grid.neighbors4((0, 0)).collect::<Vec<_>>(),
+            [(0, 1), (1, 0)]
+        );
+        assert_eq!(grid.neighbors4((1, 1)).count(), 4);
+        assert_eq!(grid.neighbors8((0, 2)).count(), 3);
+        assert_eq!(grid.neighbors8((1, 1)).count(), 8);
+        assert_eq!(SparseGrid::<()>::neighbors8((0, 0)).count(), 8);
+    }
+
+    #[test]
+    fn sparse_render() {
+        let mut grid = SparseGrid::new();
+        grid.insert((-1, -1), 'a');
+        grid.insert((1, 0), 'b');
+        assert_eq!(grid.bounds(), Some(((-1, -1), (1, 0))));
+        assert_eq!(grid.render(' ', |_, &ch| ch), "a  \n  b\n");
+    }
+
+    #[test]
+    #[should_panic(expected = "ERROR: grids must be at least one cell wide")]
+    fn from_cells_zero_width() {
+        Grid::<u8>::from_cells(0, Vec::new());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERROR: grids must be at least one cell wide")]
+    fn new_zero_width() {
+        Grid::new(0, 3, 0u8);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERROR: grids must be at least one cell wide")]
+    fn parse_empty() {
+        Grid::parse("\n\n", |ch| ch);
+    }
+}
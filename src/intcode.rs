@@ -1,6 +1,10 @@
+use std::collections::VecDeque;
 use std::sync::mpsc::{sync_channel, Receiver, Sender, SyncSender, TryRecvError};
 use std::thread;
 
+pub mod symbolic;
+pub mod transcript;
+
 #[derive(PartialEq)]
 enum Opcode {
     Add,
@@ -28,6 +32,12 @@ struct Instruction {
 
 impl Instruction {
     fn new(raw: i64) -> Self {
+        Instruction::decode(raw)
+            .unwrap_or_else(|| panic!("ERROR: {} is an invalid instruction", raw))
+    }
+
+    // None if the opcode or any of the parameter modes is unknown
+    fn decode(raw: i64) -> Option<Self> {
         // ABCDE
         //  1002
         //
@@ -47,26 +57,26 @@ impl Instruction {
             8 => Opcode::Equals,
             9 => Opcode::AdjustRelativeBase,
             99 => Opcode::Halt,
-            _ => panic!("ERROR: {} is an invaild opcode", raw),
+            _ => return None,
         };
         let parameter_modes = {
-            fn read_mode(raw_mode: i64) -> Mode {
+            fn read_mode(raw_mode: i64) -> Option<Mode> {
                 match raw_mode {
-                    0 => Mode::Position,
-                    1 => Mode::Immediate,
-                    2 => Mode::Relative,
-                    _ => panic!("ERROR: {} is an invalid parameter mode", raw_mode),
+                    0 => Some(Mode::Position),
+                    1 => Some(Mode::Immediate),
+                    2 => Some(Mode::Relative),
+                    _ => None,
                 }
             }
-            let param1 = read_mode(raw % 1000 / 100);
-            let param2 = read_mode(raw % 10_000 / 1000);
-            let param3 = read_mode(raw % 100_000 / 10_000);
+            let param1 = read_mode(raw % 1000 / 100)?;
+            let param2 = read_mode(raw % 10_000 / 1000)?;
+            let param3 = read_mode(raw % 100_000 / 10_000)?;
             vec![param1, param2, param3]
         };
-        Instruction {
+        Some(Instruction {
             opcode,
             parameter_modes,
-        }
+        })
     }
 }
 
@@ -312,6 +322,95 @@ pub fn execute_threaded_async(mut memory: Vec<i64>, input: Receiver<i64>, output
         }
     }
 }
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Status {
+    NeedsInput,
+    Halted,
+    // Ran out of steps before needing input or halting
+    Timeout,
+}
+
+// An Intcode machine run synchronously on the calling thread. Unlike the threaded executors its
+// entire state can be cloned, which makes it possible to snapshot a machine and try things out.
+#[derive(Clone)]
+pub struct Machine {
+    memory: Vec<i64>,
+    instruction_pointer: usize,
+    relative_base: i64,
+}
+
+impl Machine {
+    pub fn new(program: &[i64]) -> Self {
+        let mut memory = program.to_vec();
+        // Expand and fill with zeros
+        memory.resize(0xFFFF, 0);
+        Machine {
+            memory,
+            instruction_pointer: 0,
+            relative_base: 0,
+        }
+    }
+
+    // Run until more input is needed than is available, the program halts or `max_steps`
+    // instructions have been executed. Input is consumed from the front of `input`.
+    pub fn run(
+        &mut self,
+        input: &mut VecDeque<i64>,
+        output: &mut Vec<i64>,
+        max_steps: usize,
+    ) -> Status {
+        for _ in 0..max_steps {
+            let instruction = Instruction::new(self.memory[self.instruction_pointer]);
+            let ip = self.instruction_pointer;
+            let base = self.relative_base;
+            match instruction.opcode {
+                Opcode::Add | Opcode::Mult | Opcode::LessThan | Opcode::Equals => {
+                    let param1 = read_param(1, &instruction, ip, &self.memory, base);
+                    let param2 = read_param(2, &instruction, ip, &self.memory, base);
+                    let value = match instruction.opcode {
+                        Opcode::Add => param1 + param2,
+                        Opcode::Mult => param1 * param2,
+                        Opcode::LessThan => (param1 < param2) as i64,
+                        Opcode::Equals => (param1 == param2) as i64,
+                        _ => unreachable!(),
+                    };
+                    write_param(value, 3, &instruction, ip, &mut self.memory, base);
+                    self.instruction_pointer += 4;
+                }
+                Opcode::In => {
+                    let value = match input.pop_front() {
+                        Some(value) => value,
+                        None => return Status::NeedsInput,
+                    };
+                    write_param(value, 1, &instruction, ip, &mut self.memory, base);
+                    self.instruction_pointer += 2;
+                }
+                Opcode::Out => {
+                    output.push(read_param(1, &instruction, ip, &self.memory, base));
+                    self.instruction_pointer += 2;
+                }
+                Opcode::JumpIfTrue | Opcode::JumpIfFalse => {
+                    let param1 = read_param(1, &instruction, ip, &self.memory, base);
+                    let param2 = read_param(2, &instruction, ip, &self.memory, base);
+                    if param1 != 0 && instruction.opcode == Opcode::JumpIfTrue
+                        || param1 == 0 && instruction.opcode == Opcode::JumpIfFalse
+                    {
+                        self.instruction_pointer = param2 as usize;
+                    } else {
+                        self.instruction_pointer += 3;
+                    }
+                }
+                Opcode::AdjustRelativeBase => {
+                    self.relative_base += read_param(1, &instruction, ip, &self.memory, base);
+                    self.instruction_pointer += 2;
+                }
+                Opcode::Halt => return Status::Halted,
+            }
+        }
+        Status::Timeout
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -334,4 +433,28 @@ mod tests {
         let program = vec![104, 1125899906842624, 99];
         assert_eq!(1125899906842624, *execute(&program, &[]).last().unwrap());
     }
+
+    #[test]
+    fn machine_snapshot() {
+        // Output each input plus one, forever
+        let program = vec![3, 100, 1001, 100, 1, 100, 4, 100, 1105, 1, 0];
+        let mut machine = Machine::new(&program);
+        let mut output = Vec::new();
+        let mut input = vec![1].into_iter().collect();
+        assert_eq!(
+            machine.run(&mut input, &mut output, 100),
+            Status::NeedsInput
+        );
+        let mut snapshot = machine.clone();
+        let mut input = vec![5].into_iter().collect();
+        machine.run(&mut input, &mut output, 100);
+        let mut input = vec![10].into_iter().collect();
+        snapshot.run(&mut input, &mut output, 100);
+        assert_eq!(output, vec![2, 6, 11]);
+        let mut spinner = Machine::new(&[1105, 1, 0]);
+        assert_eq!(
+            spinner.run(&mut VecDeque::new(), &mut output, 1000),
+            Status::Timeout
+        );
+    }
 }
@@ -0,0 +1,681 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::{Add, Mul, Neg, Sub};
+
+use super::{Instruction, Mode, Opcode};
+
+// A polynomial over named symbols with integer coefficients. Monomials are stored as a sorted list
+// of symbol names, the empty monomial being the constant term. Zero terms are never stored.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Expr {
+    terms: BTreeMap<Vec<String>, i64>,
+}
+
+impl Expr {
+    pub fn constant(value: i64) -> Self {
+        let mut terms = BTreeMap::new();
+        if value != 0 {
+            terms.insert(Vec::new(), value);
+        }
+        Expr { terms }
+    }
+
+    pub fn symbol(name: &str) -> Self {
+        let mut terms = BTreeMap::new();
+        terms.insert(vec![name.to_owned()], 1);
+        Expr { terms }
+    }
+
+    pub fn as_constant(&self) -> Option<i64> {
+        match self.terms.len() {
+            0 => Some(0),
+            1 => self.terms.get(&Vec::new()).copied(),
+            _ => None,
+        }
+    }
+
+    pub fn degree(&self) -> usize {
+        self.terms.keys().map(Vec::len).max().unwrap_or(0)
+    }
+
+    pub fn is_affine(&self) -> bool {
+        self.degree() <= 1
+    }
+
+    // Coefficient of the given monomial, e.g. &["x", "y"] for the xy term or &[] for the constant
+    pub fn coefficient(&self, monomial: &[&str]) -> i64 {
+        let mut key = monomial.iter().map(|s| (*s).to_owned()).collect::<Vec<_>>();
+        key.sort();
+        *self.terms.get(&key).unwrap_or(&0)
+    }
+
+    // The value for the given symbols, None if it doesn't fit in an i64
+    pub fn eval(&self, assignment: &HashMap<&str, i64>) -> Option<i64> {
+        self.terms
+            .iter()
+            .try_fold(0i64, |sum, (monomial, coefficient)| {
+                let term = monomial.iter().try_fold(*coefficient, |acc, name| {
+                    acc.checked_mul(
+                        *assignment
+                            .get(name.as_str())
+                            .unwrap_or_else(|| panic!("ERROR: no value for symbol {}", name)),
+                    )
+                })?;
+                sum.checked_add(term)
+            })
+    }
+
+    // Replace a symbol by a constant, leaving the others symbolic
+    pub fn substitute(&self, name: &str, value: i64) -> Self {
+        let mut result = Expr::constant(0);
+        for (monomial, coefficient) in self.terms.iter() {
+            let mut remaining = Vec::new();
+            let mut coefficient = *coefficient;
+            for symbol in monomial {
+                if symbol == name {
+                    coefficient *= value;
+                } else {
+                    remaining.push(symbol.clone());
+                }
+            }
+            result.add_term(remaining, coefficient);
+        }
+        result
+    }
+
+    fn add_term(&mut self, monomial: Vec<String>, coefficient: i64) {
+        let entry = self.terms.entry(monomial).or_insert(0);
+        *entry += coefficient;
+        if *entry == 0 {
+            self.terms.retain(|_, c| *c != 0);
+        }
+    }
+}
+
+impl Add for &Expr {
+    type Output = Expr;
+
+    fn add(self, other: &Expr) -> Expr {
+        let mut result = self.clone();
+        for (monomial, coefficient) in other.terms.iter() {
+            result.add_term(monomial.clone(), *coefficient);
+        }
+        result
+    }
+}
+
+impl Neg for &Expr {
+    type Output = Expr;
+
+    fn neg(self) -> Expr {
+        Expr {
+            terms: self.terms.iter().map(|(m, c)| (m.clone(), -c)).collect(),
+        }
+    }
+}
+
+impl Sub for &Expr {
+    type Output = Expr;
+
+    fn sub(self, other: &Expr) -> Expr {
+        self + &-other
+    }
+}
+
+impl Mul for &Expr {
+    type Output = Expr;
+
+    fn mul(self, other: &Expr) -> Expr {
+        let mut result = Expr::constant(0);
+        for (monomial_a, coefficient_a) in self.terms.iter() {
+            for (monomial_b, coefficient_b) in other.terms.iter() {
+                let mut monomial = monomial_a.clone();
+                monomial.extend(monomial_b.iter().cloned());
+                monomial.sort();
+                result.add_term(monomial, coefficient_a * coefficient_b);
+            }
+        }
+        result
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.terms.is_empty() {
+            return write!(f, "0");
+        }
+        // Highest degree first, constant last
+        let mut terms = self.terms.iter().collect::<Vec<_>>();
+        terms.sort_by(|(a, _), (b, _)| b.len().cmp(&a.len()).then(a.cmp(b)));
+        for (i, (monomial, coefficient)) in terms.iter().enumerate() {
+            let magnitude = coefficient.abs();
+            if i == 0 {
+                if **coefficient < 0 {
+                    write!(f, "-")?;
+                }
+            } else if **coefficient < 0 {
+                write!(f, " - ")?;
+            } else {
+                write!(f, " + ")?;
+            }
+            if monomial.is_empty() {
+                write!(f, "{}", magnitude)?;
+            } else {
+                if magnitude != 1 {
+                    write!(f, "{}*", magnitude)?;
+                }
+                write!(f, "{}", monomial.join("*"))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Relation {
+    Zero,
+    NonZero,
+    Negative,
+    NonNegative,
+}
+
+impl Relation {
+    fn negate(self) -> Self {
+        match self {
+            Relation::Zero => Relation::NonZero,
+            Relation::NonZero => Relation::Zero,
+            Relation::Negative => Relation::NonNegative,
+            Relation::NonNegative => Relation::Negative,
+        }
+    }
+
+    fn holds(self, value: i64) -> bool {
+        match self {
+            Relation::Zero => value == 0,
+            Relation::NonZero => value != 0,
+            Relation::Negative => value < 0,
+            Relation::NonNegative => value >= 0,
+        }
+    }
+}
+
+// A fact about the inputs that held on the way down a path, e.g. `x - y < 0`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Condition {
+    pub expr: Expr,
+    pub relation: Relation,
+}
+
+impl Condition {
+    // A condition whose expression overflows can't be said to hold
+    pub fn holds(&self, assignment: &HashMap<&str, i64>) -> bool {
+        self.expr
+            .eval(assignment)
+            .is_some_and(|value| self.relation.holds(value))
+    }
+}
+
+// Split an expression into sign * base + offset, where base has no constant term and a positive
+// leading coefficient, so that e.g. `x - 8 < 0` and `-x + 5 < 0` both become bounds on `x`
+fn normalize(expr: &Expr) -> (Expr, i64, i64) {
+    let offset = expr.coefficient(&[]);
+    let mut base = expr - &Expr::constant(offset);
+    let sign = match base.terms.values().next() {
+        Some(leading) if *leading < 0 => -1,
+        _ => 1,
+    };
+    if sign < 0 {
+        base = -&base;
+    }
+    (base, sign, offset)
+}
+
+// The values of `base` with sign * base + offset satisfying the relation, as an inclusive range or
+// a single excluded value
+enum Values {
+    Range(i64, i64),
+    AllBut(i64),
+}
+
+// None if the bound doesn't fit in an i64, in which case nothing is known about `base`
+fn values(relation: Relation, sign: i64, offset: i64) -> Option<Values> {
+    let root = offset.checked_mul(-sign)?;
+    Some(match (relation, sign) {
+        (Relation::Zero, _) => Values::Range(root, root),
+        (Relation::NonZero, _) => Values::AllBut(root),
+        (Relation::Negative, 1) => Values::Range(i64::MIN, root.checked_sub(1)?),
+        (Relation::Negative, _) => Values::Range(offset.checked_add(1)?, i64::MAX),
+        (Relation::NonNegative, 1) => Values::Range(root, i64::MAX),
+        (Relation::NonNegative, _) => Values::Range(i64::MIN, offset),
+    })
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let relation = match self.relation {
+            Relation::Zero => "== 0",
+            Relation::NonZero => "!= 0",
+            Relation::Negative => "< 0",
+            Relation::NonNegative => ">= 0",
+        };
+        write!(f, "{} {}", self.expr, relation)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Termination {
+    Halted,
+    // Waiting on input after the supplied inputs ran out
+    NeedsInput,
+    // The machine tried to decode, write to or jump to a location that depends on the symbols
+    SymbolicAddress,
+    // A value read from a symbolic address reached a branch or address
+    UnknownValue,
+    // The machine tried to read from, write to or jump to a negative address
+    NegativeAddress,
+    // The machine reached a value with an unknown opcode or parameter mode
+    InvalidInstruction,
+    StepLimit,
+    PathLimit,
+}
+
+#[derive(Clone, Debug)]
+pub struct Path {
+    pub conditions: Vec<Condition>,
+    pub outputs: Vec<Expr>,
+    pub termination: Termination,
+    memory: Vec<Option<Expr>>,
+}
+
+impl Path {
+    // None if the cell holds a value read through a symbolic address
+    pub fn memory(&self, address: usize) -> Option<Expr> {
+        match self.memory.get(address) {
+            Some(value) => value.clone(),
+            None => Some(Expr::constant(0)),
+        }
+    }
+
+    pub fn satisfied_by(&self, assignment: &HashMap<&str, i64>) -> bool {
+        self.conditions.iter().all(|c| c.holds(assignment))
+    }
+}
+
+// An Intcode machine whose memory cells and inputs may be expressions over symbols. Cells that
+// were read through a symbolic address have no known value and are stored as None.
+#[derive(Clone)]
+pub struct SymbolicMachine {
+    memory: Vec<Option<Expr>>,
+    instruction_pointer: usize,
+    relative_base: i64,
+    inputs: VecDeque<Expr>,
+    outputs: Vec<Expr>,
+    conditions: Vec<Condition>,
+    steps: usize,
+}
+
+enum Step {
+    Continue,
+    Fork(Expr, Relation),
+    Stop(Termination),
+}
+
+impl SymbolicMachine {
+    pub fn new(program: &[i64]) -> Self {
+        SymbolicMachine {
+            memory: program.iter().map(|x| Some(Expr::constant(*x))).collect(),
+            instruction_pointer: 0,
+            relative_base: 0,
+            inputs: VecDeque::new(),
+            outputs: Vec::new(),
+            conditions: Vec::new(),
+            steps: 0,
+        }
+    }
+
+    pub fn set_symbol(&mut self, address: usize, name: &str) {
+        self.store(address, Some(Expr::symbol(name)));
+    }
+
+    pub fn push_input(&mut self, value: Expr) {
+        self.inputs.push_back(value);
+    }
+
+    // Run every feasible path to completion, forking whenever a branch or comparison depends on
+    // the symbols. Each path runs for at most `max_steps` and no more than `max_paths` are explored.
+    pub fn explore(self, max_steps: usize, max_paths: usize) -> Vec<Path> {
+        let mut finished = Vec::new();
+        let mut pending = vec![(self, Step::Continue)];
+        while let Some((mut machine, mut step)) = pending.pop() {
+            loop {
+                match step {
+                    Step::Continue => (),
+                    Step::Fork(expr, relation) => {
+                        if finished.len() + pending.len() + 1 >= max_paths {
+                            finished.push(machine.into_path(Termination::PathLimit));
+                            break;
+                        }
+                        let mut other = machine.clone();
+                        other.conditions.push(Condition {
+                            expr: expr.clone(),
+                            relation: relation.negate(),
+                        });
+                        let other_step = other.finish_branch(false).unwrap_or_else(Step::Stop);
+                        pending.push((other, other_step));
+                        machine.conditions.push(Condition { expr, relation });
+                        step = machine.finish_branch(true).unwrap_or_else(Step::Stop);
+                        continue;
+                    }
+                    Step::Stop(termination) => {
+                        finished.push(machine.into_path(termination));
+                        break;
+                    }
+                }
+                step = if machine.steps >= max_steps {
+                    Step::Stop(Termination::StepLimit)
+                } else {
+                    machine.step().unwrap_or_else(Step::Stop)
+                };
+            }
+        }
+        finished
+    }
+
+    fn into_path(self, termination: Termination) -> Path {
+        Path {
+            conditions: self.conditions,
+            outputs: self.outputs,
+            termination,
+            memory: self.memory,
+        }
+    }
+
+    fn load(&self, address: usize) -> Option<Expr> {
+        match self.memory.get(address) {
+            Some(value) => value.clone(),
+            None => Some(Expr::constant(0)),
+        }
+    }
+
+    fn store(&mut self, address: usize, value: Option<Expr>) {
+        if address >= self.memory.len() {
+            self.memory.resize(address + 1, Some(Expr::constant(0)));
+        }
+        self.memory[address] = value;
+    }
+
+    fn concrete(&self, address: usize) -> Option<i64> {
+        self.load(address).and_then(|value| value.as_constant())
+    }
+
+    // The address a parameter refers to. Stops the path if that depends on the symbols or is
+    // negative.
+    fn param_address(
+        &self,
+        param_idx: usize,
+        instruction: &Instruction,
+    ) -> Result<usize, Termination> {
+        let param = self
+            .concrete(self.instruction_pointer + param_idx)
+            .ok_or(Termination::SymbolicAddress)?;
+        let address = match instruction.parameter_modes[param_idx - 1] {
+            Mode::Position => param,
+            Mode::Immediate => return Err(Termination::SymbolicAddress),
+            Mode::Relative => param + self.relative_base,
+        };
+        usize::try_from(address).map_err(|_| Termination::NegativeAddress)
+    }
+
+    // The value of a parameter, None if it was read from an address depending on the symbols
+    fn read_param(
+        &self,
+        param_idx: usize,
+        instruction: &Instruction,
+    ) -> Result<Option<Expr>, Termination> {
+        match instruction.parameter_modes[param_idx - 1] {
+            Mode::Immediate => Ok(self.load(self.instruction_pointer + param_idx)),
+            _ => match self.param_address(param_idx, instruction) {
+                Ok(address) => Ok(self.load(address)),
+                Err(Termination::SymbolicAddress) => Ok(None),
+                Err(termination) => Err(termination),
+            },
+        }
+    }
+
+    // Decide a relation on the current path if possible, either because the expression is
+    // constant or because the earlier conditions bound it to one side
+    fn decide(&self, expr: &Expr, relation: Relation) -> Option<bool> {
+        if let Some(value) = expr.as_constant() {
+            return Some(relation.holds(value));
+        }
+        let (base, sign, offset) = normalize(expr);
+        let (mut low, mut high) = (i64::MIN, i64::MAX);
+        let mut excluded = Vec::new();
+        for condition in self.conditions.iter() {
+            let (other_base, other_sign, other_offset) = normalize(&condition.expr);
+            if other_base != base {
+                continue;
+            }
+            match values(condition.relation, other_sign, other_offset) {
+                Some(Values::Range(l, h)) => {
+                    low = low.max(l);
+                    high = high.min(h);
+                }
+                Some(Values::AllBut(value)) => excluded.push(value),
+                None => (),
+            }
+        }
+        while low < high && excluded.contains(&low) {
+            low += 1;
+        }
+        while low < high && excluded.contains(&high) {
+            high -= 1;
+        }
+        match values(relation, sign, offset)? {
+            Values::Range(l, h) if l <= low && high <= h => Some(true),
+            Values::Range(l, h) if l.max(low) > h.min(high) => Some(false),
+            Values::AllBut(value) if value < low || value > high => Some(true),
+            Values::AllBut(value) if excluded.contains(&value) => Some(true),
+            Values::AllBut(value) if low == value && high == value => Some(false),
+            _ => None,
+        }
+    }
+
+    fn instruction(&self) -> Result<Instruction, Termination> {
+        let raw = self
+            .concrete(self.instruction_pointer)
+            .ok_or(Termination::SymbolicAddress)?;
+        Instruction::decode(raw).ok_or(Termination::InvalidInstruction)
+    }
+
+    // Finish a comparison or jump once the outcome of its condition is known
+    fn finish_branch(&mut self, outcome: bool) -> Result<Step, Termination> {
+        let instruction = &self.instruction()?;
+        match instruction.opcode {
+            Opcode::LessThan | Opcode::Equals => {
+                let address = self.param_address(3, instruction)?;
+                self.store(address, Some(Expr::constant(outcome as i64)));
+                self.instruction_pointer += 4;
+            }
+            Opcode::JumpIfTrue | Opcode::JumpIfFalse => {
+                let jump = outcome == (instruction.opcode == Opcode::JumpIfTrue);
+                if jump {
+                    let target = self
+                        .read_param(2, instruction)?
+                        .and_then(|target| target.as_constant())
+                        .ok_or(Termination::SymbolicAddress)?;
+                    self.instruction_pointer =
+                        usize::try_from(target).map_err(|_| Termination::NegativeAddress)?;
+                } else {
+                    self.instruction_pointer += 3;
+                }
+            }
+            _ => unreachable!(),
+        }
+        self.steps += 1;
+        Ok(Step::Continue)
+    }
+
+    // Run one instruction. An error is how the path ends, halting included.
+    fn step(&mut self) -> Result<Step, Termination> {
+        let instruction = self.instruction()?;
+        match instruction.opcode {
+            Opcode::Add | Opcode::Mult => {
+                let value = match (
+                    self.read_param(1, &instruction)?,
+                    self.read_param(2, &instruction)?,
+                ) {
+                    (Some(a), Some(b)) if instruction.opcode == Opcode::Add => Some(&a + &b),
+                    (Some(a), Some(b)) => Some(&a * &b),
+                    _ => None,
+                };
+                let address = self.param_address(3, &instruction)?;
+                self.store(address, value);
+                self.instruction_pointer += 4;
+            }
+            Opcode::LessThan | Opcode::Equals => {
+                let (a, b) = match (
+                    self.read_param(1, &instruction)?,
+                    self.read_param(2, &instruction)?,
+                ) {
+                    (Some(a), Some(b)) => (a, b),
+                    _ => return Err(Termination::UnknownValue),
+                };
+                let (expr, relation) = if instruction.opcode == Opcode::LessThan {
+                    (&a - &b, Relation::Negative)
+                } else {
+                    (&a - &b, Relation::Zero)
+                };
+                return match self.decide(&expr, relation) {
+                    Some(outcome) => self.finish_branch(outcome),
+                    None => Ok(Step::Fork(expr, relation)),
+                };
+            }
+            Opcode::JumpIfTrue | Opcode::JumpIfFalse => {
+                let value = self
+                    .read_param(1, &instruction)?
+                    .ok_or(Termination::UnknownValue)?;
+                return match self.decide(&value, Relation::NonZero) {
+                    Some(outcome) => self.finish_branch(outcome),
+                    None => Ok(Step::Fork(value, Relation::NonZero)),
+                };
+            }
+            Opcode::In => {
+                let value = self.inputs.pop_front().ok_or(Termination::NeedsInput)?;
+                let address = self.param_address(1, &instruction)?;
+                self.store(address, Some(value));
+                self.instruction_pointer += 2;
+            }
+            Opcode::Out => {
+                let value = self
+                    .read_param(1, &instruction)?
+                    .ok_or(Termination::UnknownValue)?;
+                self.outputs.push(value);
+                self.instruction_pointer += 2;
+            }
+            Opcode::AdjustRelativeBase => {
+                self.relative_base += self
+                    .read_param(1, &instruction)?
+                    .and_then(|value| value.as_constant())
+                    .ok_or(Termination::SymbolicAddress)?;
+                self.instruction_pointer += 2;
+            }
+            Opcode::Halt => return Err(Termination::Halted),
+        }
+        self.steps += 1;
+        Ok(Step::Continue)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expr_arithmetic() {
+        let x = Expr::symbol("x");
+        let y = Expr::symbol("y");
+        let sum = &(&x + &y) + &Expr::constant(3);
+        let product = &sum * &x;
+        assert_eq!(product.to_string(), "x*x + x*y + 3*x");
+        assert_eq!((&sum - &sum).as_constant(), Some(0));
+        assert!(!product.is_affine());
+        assert_eq!(product.substitute("y", 2).to_string(), "x*x + 5*x");
+    }
+
+    #[test]
+    fn day2_style_program_is_affine() {
+        // memory[3] = memory[noun] + memory[verb] is overwritten before memory[0] = 5 * (noun + verb)
+        let program = vec![1, 0, 0, 3, 1, 1, 2, 3, 2, 3, 13, 0, 99, 5];
+        let mut machine = SymbolicMachine::new(&program);
+        machine.set_symbol(1, "noun");
+        machine.set_symbol(2, "verb");
+        let paths = machine.explore(1000, 10);
+        assert_eq!(paths.len(), 1);
+        assert_eq!(paths[0].termination, Termination::Halted);
+        let result = paths[0].memory(0).unwrap();
+        assert!(result.is_affine());
+        assert_eq!(result.to_string(), "5*noun + 5*verb");
+    }
+
+    #[test]
+    fn negative_addresses_stop() {
+        // Read from -1, write to -5 and jump to -2
+        for program in [
+            vec![1, -1, 0, 0, 99],
+            vec![1101, 1, 1, -5, 99],
+            vec![1105, 1, -2, 99],
+        ] {
+            let paths = SymbolicMachine::new(&program).explore(1000, 10);
+            assert_eq!(paths[0].termination, Termination::NegativeAddress);
+        }
+    }
+
+    #[test]
+    fn invalid_instructions_stop() {
+        // Jump to an unknown opcode, then to an unknown parameter mode
+        for program in [vec![1105, 1, 3, 42], vec![1105, 1, 3, 301]] {
+            let paths = SymbolicMachine::new(&program).explore(1000, 10);
+            assert_eq!(paths[0].termination, Termination::InvalidInstruction);
+        }
+    }
+
+    #[test]
+    fn overflowing_values_are_unknown() {
+        let x = Expr::symbol("x");
+        let square = &x * &x;
+        let assignment = [("x", i64::MAX)].iter().cloned().collect();
+        assert_eq!(square.eval(&assignment), None);
+        let condition = Condition {
+            expr: square,
+            relation: Relation::NonNegative,
+        };
+        assert!(!condition.holds(&assignment));
+        // Deciding x + i64::MIN == 0 needs x == 2^63, which doesn't fit
+        assert!(values(Relation::Zero, 1, i64::MIN).is_none());
+        assert!(values(Relation::Negative, 1, i64::MIN).is_none());
+        assert!(values(Relation::Negative, -1, i64::MAX).is_none());
+    }
+
+    #[test]
+    fn branches_fork_with_conditions() {
+        // Output 1 if the input is less than 8, 0 otherwise
+        let program = vec![3, 9, 7, 9, 10, 9, 4, 9, 99, -1, 8];
+        let mut machine = SymbolicMachine::new(&program);
+        machine.push_input(Expr::symbol("x"));
+        let paths = machine.explore(1000, 10);
+        assert_eq!(paths.len(), 2);
+        for path in paths.iter() {
+            assert_eq!(path.termination, Termination::Halted);
+            assert_eq!(path.conditions.len(), 1);
+            let output = path.outputs[0].as_constant().unwrap();
+            let expected = if output == 1 {
+                "x - 8 < 0"
+            } else {
+                "x - 8 >= 0"
+            };
+            assert_eq!(path.conditions[0].to_string(), expected);
+        }
+    }
+}
@@ -0,0 +1,325 @@
+use crate::intcode::{Machine, Status};
+use crate::options;
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::mpsc::{channel, sync_channel, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// A transcript file looks like this, with the time since the start of the session in milliseconds
+// before each input:
+//
+// < Command?
+// > 1523 north
+// < == Hull Breach ==
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    Input { elapsed: Duration, line: String },
+    Output(String),
+}
+
+#[derive(Default)]
+pub struct Transcript {
+    events: Vec<Event>,
+    // If set, every event is also written here as soon as it happens
+    file: Option<File>,
+}
+
+impl Transcript {
+    // Record to a new file, failing if `path` already exists
+    pub fn record<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Transcript {
+            events: Vec::new(),
+            file: Some(File::options().write(true).create_new(true).open(path)?),
+        })
+    }
+
+    // Record to <dir>/<name>.txt, or <dir>/<name>-2.txt and so on if that is taken, so no earlier
+    // transcript is ever overwritten
+    fn record_unused(dir: &Path, name: &str) -> io::Result<Self> {
+        for n in 1.. {
+            let file = match n {
+                1 => format!("{}.txt", name),
+                n => format!("{}-{}.txt", name, n),
+            };
+            match Transcript::record(dir.join(file)) {
+                Err(error) if error.kind() == io::ErrorKind::AlreadyExists => continue,
+                result => return result,
+            }
+        }
+        unreachable!()
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut events = Vec::new();
+        for line in BufReader::new(File::open(path)?).lines() {
+            let line = line?;
+            let invalid = || io::Error::new(io::ErrorKind::InvalidData, line.clone());
+            if let Some(output) = line.strip_prefix("< ") {
+                events.push(Event::Output(output.to_owned()));
+            } else if let Some(input) = line.strip_prefix("> ") {
+                let mut parts = input.splitn(2, ' ');
+                let millis = parts.next().unwrap().parse().map_err(|_| invalid())?;
+                events.push(Event::Input {
+                    elapsed: Duration::from_millis(millis),
+                    line: parts.next().unwrap_or("").to_owned(),
+                });
+            } else {
+                return Err(invalid());
+            }
+        }
+        Ok(Transcript { events, file: None })
+    }
+
+    pub fn outputs(&self) -> impl Iterator<Item = &str> {
+        self.events.iter().filter_map(|event| match event {
+            Event::Output(line) => Some(line.as_str()),
+            Event::Input { .. } => None,
+        })
+    }
+
+    pub fn inputs(&self) -> impl Iterator<Item = &str> {
+        self.events.iter().filter_map(|event| match event {
+            Event::Input { line, .. } => Some(line.as_str()),
+            Event::Output(_) => None,
+        })
+    }
+
+    // Keep only the first `inputs` inputs along with the output that followed the last of them
+    pub fn truncate(&mut self, inputs: usize) {
+        let end = self
+            .events
+            .iter()
+            .enumerate()
+            .filter(|(_, event)| matches!(event, Event::Input { .. }))
+            .nth(inputs)
+            .map(|(i, _)| i)
+            .unwrap_or_else(|| self.events.len());
+        self.events.truncate(end);
+    }
+
+    // Index of the first event that differs from `other`, ignoring timestamps
+    pub fn diverges_at(&self, other: &Transcript) -> Option<usize> {
+        self.events
+            .iter()
+            .zip(other.events.iter())
+            .position(|pair| match pair {
+                (Event::Input { line: a, .. }, Event::Input { line: b, .. }) => a != b,
+                (a, b) => a != b,
+            })
+    }
+
+    pub fn push(&mut self, event: Event) {
+        if let Some(file) = &mut self.file {
+            match &event {
+                Event::Input { elapsed, line } => {
+                    writeln!(file, "> {} {}", elapsed.as_millis(), line)
+                }
+                Event::Output(line) => writeln!(file, "< {}", line),
+            }
+            .expect("ERROR: failed to write transcript");
+        }
+        self.events.push(event);
+    }
+}
+
+// What the machine thread reports, in the order it happens. The channel closes once the program
+// halts.
+enum MachineEvent {
+    Output(i64),
+    NeedsInput,
+}
+
+// Run `program` on its own thread. Outputs and requests for input share one channel, so they can
+// be waited on together, and every line sent back is fed to the program.
+fn spawn_machine(program: &[i64]) -> (Sender<Vec<i64>>, Receiver<MachineEvent>) {
+    let (tx_line, rx_line) = channel::<Vec<i64>>();
+    let (tx_event, rx_event) = sync_channel(0);
+    let mut machine = Machine::new(program);
+    thread::spawn(move || {
+        let mut input = VecDeque::new();
+        let mut output = Vec::new();
+        loop {
+            // Run in bursts so output shows up while the program is still busy
+            let status = machine.run(&mut input, &mut output, 10_000);
+            for value in output.drain(..) {
+                if tx_event.send(MachineEvent::Output(value)).is_err() {
+                    return;
+                }
+            }
+            match status {
+                Status::Halted => return,
+                Status::NeedsInput => {
+                    if tx_event.send(MachineEvent::NeedsInput).is_err() {
+                        return;
+                    }
+                    match rx_line.recv() {
+                        Ok(line) => input.extend(line),
+                        Err(_) => return,
+                    }
+                }
+                Status::Timeout => (),
+            }
+        }
+    });
+    (tx_line, rx_event)
+}
+
+// Run an ASCII Intcode program, feeding it `inputs` line by line and then lines from stdin if
+// `interactive` is set. Everything sent and received is recorded to `transcript`. Returns the
+// first non-ASCII output, or None if the program halts or runs out of input first.
+pub fn run_session(
+    program: &[i64],
+    inputs: &[String],
+    transcript: &mut Transcript,
+    interactive: bool,
+) -> Option<i64> {
+    let (tx_line, events) = spawn_machine(program);
+    let start = Instant::now();
+    let mut inputs = inputs.iter().cloned();
+    let mut output_line = String::new();
+    let finish_line = |transcript: &mut Transcript, output_line: &mut String| {
+        if !output_line.is_empty() {
+            transcript.push(Event::Output(output_line.split_off(0)));
+        }
+    };
+    for event in events {
+        match event {
+            MachineEvent::NeedsInput => {
+                finish_line(transcript, &mut output_line);
+                let line = match inputs.next() {
+                    Some(line) => {
+                        println!("{}", line);
+                        line
+                    }
+                    None if interactive => {
+                        let mut line = String::new();
+                        if std::io::stdin().read_line(&mut line).unwrap() == 0 {
+                            return None;
+                        }
+                        line.trim_end_matches('\n').to_owned()
+                    }
+                    None => return None,
+                };
+                let values = line.chars().chain(std::iter::once('\n'));
+                let values = values.map(|ch| ch as i64).collect();
+                transcript.push(Event::Input {
+                    elapsed: start.elapsed(),
+                    line,
+                });
+                tx_line.send(values).expect("ERROR: failed to send input");
+            }
+            MachineEvent::Output(out) if (0..128).contains(&out) => {
+                let ch = std::char::from_u32(out as u32).unwrap();
+                print!("{}", ch);
+                if ch == '\n' {
+                    transcript.push(Event::Output(output_line.split_off(0)));
+                } else {
+                    output_line.push(ch);
+                }
+            }
+            MachineEvent::Output(out) => {
+                finish_line(transcript, &mut output_line);
+                return Some(out);
+            }
+        }
+    }
+    finish_line(transcript, &mut output_line);
+    None
+}
+
+// Run the interactive session for `day`, recorded to transcripts/day<day>.txt, or
+// transcripts/day<day>-2.txt and so on once that exists, starting with the lines of `script`. If
+// AOC_DAY<day>_REPLAY names a transcript file its inputs are used instead of the script, and if
+// AOC_DAY<day>_REPLAY_INPUTS is also set only that many of them are replayed before continuing
+// from stdin. Redirect stdin from /dev/null to replay without interaction.
+pub fn interactive_session(program: &[i64], day: u32, script: &str) -> (Option<i64>, Transcript) {
+    let replayed = options::get(day, "REPLAY").map(|path| {
+        let mut replayed = Transcript::load(&path)
+            .unwrap_or_else(|err| panic!("ERROR: failed to read {}: {}", path, err));
+        if let Some(inputs) = options::get(day, "REPLAY_INPUTS") {
+            replayed.truncate(inputs.parse().unwrap_or_else(|_| {
+                panic!("ERROR: invalid AOC_DAY{}_REPLAY_INPUTS {:?}", day, inputs)
+            }));
+        }
+        replayed
+    });
+    let inputs = match &replayed {
+        Some(replayed) => replayed.inputs().map(str::to_owned).collect(),
+        None => script.lines().map(str::to_owned).collect::<Vec<_>>(),
+    };
+
+    fs::create_dir_all("transcripts").expect("ERROR: failed to create transcripts/");
+    let name = format!("day{}", day);
+    let mut transcript = Transcript::record_unused(Path::new("transcripts"), &name)
+        .expect("ERROR: failed to create transcript");
+    let result = run_session(program, &inputs, &mut transcript, true);
+
+    if let Some(index) = replayed.and_then(|replayed| replayed.diverges_at(&transcript)) {
+        eprintln!(
+            "WARNING: replay diverged from the transcript at event {}",
+            index
+        );
+    }
+    (result, transcript)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    // Echoes every character it reads
+    const ECHO: [i64; 7] = [3, 100, 4, 100, 1105, 1, 0];
+
+    #[test]
+    fn record_and_replay() {
+        let inputs = vec!["north".to_owned(), "take mug".to_owned()];
+        let mut transcript = Transcript::default();
+        assert_eq!(run_session(&ECHO, &inputs, &mut transcript, false), None);
+        assert_eq!(transcript.inputs().collect::<Vec<_>>(), inputs);
+        assert_eq!(transcript.events[1], Event::Output("north".to_owned()),);
+
+        let replayed_inputs = transcript.inputs().map(str::to_owned).collect::<Vec<_>>();
+        let mut replayed = Transcript::default();
+        run_session(&ECHO, &replayed_inputs, &mut replayed, false);
+        assert_eq!(transcript.diverges_at(&replayed), None);
+    }
+
+    #[test]
+    fn record_never_overwrites() {
+        let dir = env::temp_dir().join(format!("aoc2019-transcripts-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("day25.txt"), "< Command?\n").unwrap();
+        let error = Transcript::record(dir.join("day25.txt")).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::AlreadyExists);
+        let mut transcript = Transcript::record_unused(&dir, "day25").unwrap();
+        transcript.push(Event::Output("== Hull Breach ==".to_owned()));
+        Transcript::record_unused(&dir, "day25").unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("day25.txt")).unwrap(),
+            "< Command?\n"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("day25-2.txt")).unwrap(),
+            "< == Hull Breach ==\n"
+        );
+        assert!(dir.join("day25-3.txt").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn truncate_keeps_following_output() {
+        let inputs = vec!["a".to_owned(), "b".to_owned(), "c".to_owned()];
+        let mut transcript = Transcript::default();
+        run_session(&ECHO, &inputs, &mut transcript, false);
+        transcript.truncate(2);
+        assert_eq!(transcript.inputs().collect::<Vec<_>>(), vec!["a", "b"]);
+        assert_eq!(
+            transcript.events.last(),
+            Some(&Event::Output("b".to_owned()))
+        );
+    }
+}
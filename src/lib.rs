@@ -1,7 +1,13 @@
-#![feature(is_sorted)]
 use aoc_runner_derive::aoc_lib;
 
+mod grid;
 mod intcode;
+mod ocr;
+mod options;
+mod orbits;
+mod search;
+mod sif;
 mod solutions;
+mod springscript;
 
 aoc_lib! { year = 2019 }
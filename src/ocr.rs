@@ -0,0 +1,463 @@
+// Reading the block letters some puzzles draw their answers in
+use crate::grid::Grid;
+use std::fmt;
+
+// The letters of the 6 tall font, each in a cell 5 wide including the gap after it. The pixels
+// are listed row by row.
+const SMALL: [(char, &str); 18] = [
+    ('A', ".##..#..#.#..#.####.#..#.#..#."),
+    ('B', "###..#..#.###..#..#.#..#.###.."),
+    ('C', ".##..#..#.#....#....#..#..##.."),
+    ('E', "####.#....###..#....#....####."),
+    ('F', "####.#....###..#....#....#...."),
+    ('G', ".##..#..#.#....#.##.#..#..###."),
+    ('H', "#..#.#..#.####.#..#.#..#.#..#."),
+    ('I', ".###...#....#....#....#...###."),
+    ('J', "..##....#....#....#.#..#..##.."),
+    ('K', "#..#.#.#..##...#.#..#.#..#..#."),
+    ('L', "#....#....#....#....#....####."),
+    ('O', ".##..#..#.#..#.#..#.#..#..##.."),
+    ('P', "###..#..#.#..#.###..#....#...."),
+    ('R', "###..#..#.#..#.###..#.#..#..#."),
+    ('S', ".###.#....#.....##.....#.###.."),
+    ('U', "#..#.#..#.#..#.#..#.#..#..##.."),
+    ('Y', "#...##...#.#.#...#....#....#.."),
+    ('Z', "####....#...#...#...#....####."),
+];
+
+// The letters of the 10 tall font, each in a cell 8 wide including the gap after it
+const LARGE: [(char, &str); 15] = [
+    (
+        'A',
+        "..##....\
+         .#..#...\
+         #....#..\
+         #....#..\
+         #....#..\
+         ######..\
+         #....#..\
+         #....#..\
+         #....#..\
+         #....#..",
+    ),
+    (
+        'B',
+        "#####...\
+         #....#..\
+         #....#..\
+         #....#..\
+         #####...\
+         #....#..\
+         #....#..\
+         #....#..\
+         #....#..\
+         #####...",
+    ),
+    (
+        'C',
+        ".####...\
+         #....#..\
+         #.......\
+         #.......\
+         #.......\
+         #.......\
+         #.......\
+         #.......\
+         #....#..\
+         .####...",
+    ),
+    (
+        'E',
+        "######..\
+         #.......\
+         #.......\
+         #.......\
+         #####...\
+         #.......\
+         #.......\
+         #.......\
+         #.......\
+         ######..",
+    ),
+    (
+        'F',
+        "######..\
+         #.......\
+         #.......\
+         #.......\
+         #####...\
+         #.......\
+         #.......\
+         #.......\
+         #.......\
+         #.......",
+    ),
+    (
+        'G',
+        ".####...\
+         #....#..\
+         #.......\
+         #.......\
+         #.......\
+         #..###..\
+         #....#..\
+         #....#..\
+         #...##..\
+         .###.#..",
+    ),
+    (
+        'H',
+        "#....#..\
+         #....#..\
+         #....#..\
+         #....#..\
+         ######..\
+         #....#..\
+         #....#..\
+         #....#..\
+         #....#..\
+         #....#..",
+    ),
+    (
+        'J',
+        "...###..\
+         ....#...\
+         ....#...\
+         ....#...\
+         ....#...\
+         ....#...\
+         ....#...\
+         #...#...\
+         #...#...\
+         .###....",
+    ),
+    (
+        'K',
+        "#....#..\
+         #...#...\
+         #..#....\
+         #.#.....\
+         ##......\
+         ##......\
+         #.#.....\
+         #..#....\
+         #...#...\
+         #....#..",
+    ),
+    (
+        'L',
+        "#.......\
+         #.......\
+         #.......\
+         #.......\
+         #.......\
+         #.......\
+         #.......\
+         #.......\
+         #.......\
+         ######..",
+    ),
+    (
+        'N',
+        "#....#..\
+         ##...#..\
+         ##...#..\
+         #.#..#..\
+         #.#..#..\
+         #..#.#..\
+         #..#.#..\
+         #...##..\
+         #...##..\
+         #....#..",
+    ),
+    (
+        'P',
+        "#####...\
+         #....#..\
+         #....#..\
+         #....#..\
+         #####...\
+         #.......\
+         #.......\
+         #.......\
+         #.......\
+         #.......",
+    ),
+    (
+        'R',
+        "#####...\
+         #....#..\
+         #....#..\
+         #....#..\
+         #####...\
+         #..#....\
+         #...#...\
+         #...#...\
+         #....#..\
+         #....#..",
+    ),
+    (
+        'X',
+        "#....#..\
+         #....#..\
+         .#..#...\
+         .#..#...\
+         ..##....\
+         ..##....\
+         .#..#...\
+         .#..#...\
+         #....#..\
+         #....#..",
+    ),
+    (
+        'Z',
+        "######..\
+         .....#..\
+         .....#..\
+         ....#...\
+         ...#....\
+         ..#.....\
+         .#......\
+         #.......\
+         #.......\
+         ######..",
+    ),
+];
+
+// A glyph that isn't in the font
+#[derive(Debug, PartialEq)]
+pub struct UnknownGlyph {
+    // Which letter of the text it is, counting from 0
+    pub index: usize,
+    // The glyph's cell, one row per line with '#' for lit pixels
+    pub bitmap: String,
+}
+
+impl fmt::Display for UnknownGlyph {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "unrecognized glyph {}:", self.index)?;
+        write!(f, "{}", self.bitmap)
+    }
+}
+
+// Why some text couldn't be read
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    // The height of the text in pixels, which doesn't match any font
+    UnsupportedHeight(usize),
+    UnknownGlyphs(Vec<UnknownGlyph>),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::UnsupportedHeight(height) => write!(f, "no font is {} pixels tall", height),
+            Error::UnknownGlyphs(glyphs) => {
+                for glyph in glyphs {
+                    write!(f, "{}", glyph)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+struct Font {
+    glyphs: &'static [(char, &'static str)],
+    // The size of a cell, including the gap after the letter
+    width: usize,
+    height: usize,
+}
+
+// Read the letters written by the lit pixels. The font is picked by the height of the text, which
+// may sit anywhere in the grid.
+pub fn read(pixels: &Grid<bool>) -> Result<String, Error> {
+    let lit = pixels
+        .iter()
+        .filter(|(_, lit)| **lit)
+        .map(|(point, _)| point)
+        .collect::<Vec<_>>();
+    if lit.is_empty() {
+        return Ok(String::new());
+    }
+    let min_x = lit.iter().map(|(x, _)| *x).min().unwrap();
+    let max_x = lit.iter().map(|(x, _)| *x).max().unwrap();
+    let min_y = lit.iter().map(|(_, y)| *y).min().unwrap();
+    let max_y = lit.iter().map(|(_, y)| *y).max().unwrap();
+    let font = match max_y - min_y + 1 {
+        6 => Font {
+            glyphs: &SMALL,
+            width: 5,
+            height: 6,
+        },
+        10 => Font {
+            glyphs: &LARGE,
+            width: 8,
+            height: 10,
+        },
+        other => return Err(Error::UnsupportedHeight(other)),
+    };
+
+    // Letters like I and J leave blank columns at the start of their cell, so the first lit column
+    // needn't be where a cell starts. Try cells lined up with x = 0 first, then every other start
+    // that still puts the first lit column in the first cell.
+    let aligned = (min_x - min_x % font.width) as isize;
+    let starts = Some(aligned).into_iter().chain(
+        (0..font.width as isize)
+            .map(|shift| min_x as isize - shift)
+            .filter(|&start| start != aligned),
+    );
+    let mut first_unknown = None;
+    for start in starts {
+        match read_cells(pixels, &font, start, max_x, min_y) {
+            Ok(text) => return Ok(text),
+            Err(unknown) => {
+                first_unknown.get_or_insert(unknown);
+            }
+        }
+    }
+    Err(Error::UnknownGlyphs(first_unknown.unwrap()))
+}
+
+// Read the cells from column `start` until past column `end`, with their tops in row `top`. Cells
+// may start left of the grid, where every pixel is unlit.
+fn read_cells(
+    pixels: &Grid<bool>,
+    font: &Font,
+    start: isize,
+    end: usize,
+    top: usize,
+) -> Result<String, Vec<UnknownGlyph>> {
+    let mut text = String::new();
+    let mut unknown = Vec::new();
+    for (index, left) in (start..=end as isize).step_by(font.width).enumerate() {
+        let mut bitmap = String::new();
+        for y in top..top + font.height {
+            for x in left..left + font.width as isize {
+                let lit = x >= 0 && *pixels.get((x as usize, y)).unwrap_or(&false);
+                bitmap.push(if lit { '#' } else { '.' });
+            }
+        }
+        let glyph = font.glyphs.iter().find(|(_, pixels)| *pixels == bitmap);
+        match glyph {
+            Some((letter, _)) => text.push(*letter),
+            None => unknown.push(UnknownGlyph {
+                index,
+                bitmap: bitmap
+                    .as_bytes()
+                    .chunks(font.width)
+                    .map(|row| String::from_utf8_lossy(row) + "\n")
+                    .collect(),
+            }),
+        }
+    }
+    if unknown.is_empty() {
+        Ok(text)
+    } else {
+        Err(unknown)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_small() {
+        let pixels = Grid::parse(
+            "\n\
+             .#..#..###.#...#\n\
+             .#..#...#..#...#\n\
+             .####...#...#.#.\n\
+             .#..#...#....#..\n\
+             .#..#...#....#..\n\
+             .#..#..###...#..\n",
+            |ch| ch == '#',
+        );
+        assert_eq!(read(&pixels), Ok("HIY".to_owned()));
+    }
+
+    #[test]
+    fn read_large() {
+        let rows = [
+            "#....#..#.....",
+            "#....#..#.....",
+            ".#..#...#.....",
+            ".#..#...#.....",
+            "..##....#.....",
+            "..##....#.....",
+            ".#..#...#.....",
+            ".#..#...#.....",
+            "#....#..#.....",
+            "#....#..######",
+        ];
+        let pixels = Grid::parse(&rows.join("\n"), |ch| ch == '#');
+        assert_eq!(read(&pixels), Ok("XL".to_owned()));
+    }
+
+    #[test]
+    fn unknown_glyphs() {
+        let pixels = Grid::parse("#..#.#\n#..#.#\n####.#\n#..#.#\n#..#.#\n#..#.#\n", |ch| {
+            ch == '#'
+        });
+        let unknown = match read(&pixels) {
+            Err(Error::UnknownGlyphs(unknown)) => unknown,
+            other => panic!("expected unknown glyphs, got {:?}", other),
+        };
+        assert_eq!(unknown.len(), 1);
+        assert_eq!(unknown[0].index, 1);
+        assert_eq!(
+            unknown[0].to_string(),
+            "unrecognized glyph 1:\n#....\n#....\n#....\n#....\n#....\n#....\n"
+        );
+    }
+
+    #[test]
+    fn read_leading_blank_columns() {
+        // I and J start with blank columns, in a small font image lined up with x = 0
+        let pixels = Grid::parse(
+            ".###...##.#..#.\n\
+             ..#.....#.#..#.\n\
+             ..#.....#.####.\n\
+             ..#.....#.#..#.\n\
+             ..#..#..#.#..#.\n\
+             .###..##..#..#.\n",
+            |ch| ch == '#',
+        );
+        assert_eq!(read(&pixels), Ok("IJH".to_owned()));
+        // The same text shifted right, so the cells don't line up with x = 0
+        let shifted = Grid::parse(
+            &pixels
+                .render(|_, &lit| if lit { '#' } else { '.' })
+                .lines()
+                .map(|line| format!("...{}\n", line))
+                .collect::<String>(),
+            |ch| ch == '#',
+        );
+        assert_eq!(read(&shifted), Ok("IJH".to_owned()));
+    }
+
+    #[test]
+    fn read_large_starting_with_j() {
+        let rows = [
+            "...###..#....#..",
+            "....#...#....#..",
+            "....#...#....#..",
+            "....#...#....#..",
+            "....#...######..",
+            "....#...#....#..",
+            "....#...#....#..",
+            "#...#...#....#..",
+            "#...#...#....#..",
+            ".###....#....#..",
+        ];
+        let pixels = Grid::parse(&rows.join("\n"), |ch| ch == '#');
+        assert_eq!(read(&pixels), Ok("JH".to_owned()));
+    }
+
+    #[test]
+    fn unsupported_height() {
+        let pixels = Grid::parse("#\n#\n#\n", |ch| ch == '#');
+        assert_eq!(read(&pixels), Err(Error::UnsupportedHeight(3)));
+    }
+}
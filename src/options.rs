@@ -0,0 +1,48 @@
+// Optional output from the solvers. Each is turned on by an environment variable named
+// AOC_DAY<day>_<NAME>, so an unset variable always means a solver only returns its answer.
+//
+// AOC_DAY3_REPORT      any value, print every crossing of each pair of wires
+// AOC_DAY3_RENDER      "text:<file>" or "svg:<file>", a drawing of the wires
+// AOC_DAY6_DOT         <file>, the orbit map in the graphviz DOT format
+// AOC_DAY8_IMAGE       any value, print the decoded image
+// AOC_DAY8_PGM         <file>, the composited image as a plain PGM
+// AOC_DAY11_HULL       any value, print the painted hull
+// AOC_DAY11_PBM        <file>, the painted hull as a plain PBM
+// AOC_DAY11_FRAMES     <directory>, one text file for every panel painted
+// AOC_DAY13_CONTROLLER "keyboard", "tracking", "predictive" or "replay:<file>", who plays
+// AOC_DAY13_DISPLAY    "curses" for the live view, the default on a terminal, or "headless"
+// AOC_DAY13_SCREEN     any value, print the last screen and how long the game took
+// AOC_DAY13_RECORD     <file>, the joystick moves played
+// AOC_DAY15_FLOOD      "curses", "text:<directory>" or "ppm:<directory>", the oxygen spreading
+// AOC_DAY15_ROUTE      any value, print the shortest route to the oxygen system
+// AOC_DAY15_STATS      any value, print how many tiles the oxygen fills every minute
+// AOC_DAY18_ROUTE      any value, print the shortest route and the order the keys are collected in
+// AOC_DAY20_ROUTE      any value, print the shortest route through the maze
+// AOC_DAY21_REPLAY     <file>, a transcript whose inputs the springdroid session replays
+// AOC_DAY21_REPLAY_INPUTS <count>, replay only this many of the inputs, then read stdin
+// AOC_DAY25_MAP        any value, print the ship map drawn as rooms and corridors
+// AOC_DAY25_DOT        <file>, the ship map in the graphviz DOT format
+// AOC_DAY25_REPLAY     <file>, a transcript whose inputs the droid session replays
+// AOC_DAY25_REPLAY_INPUTS <count>, replay only this many of the inputs, then read stdin
+
+use std::env;
+use std::fs;
+
+pub fn get(day: u32, name: &str) -> Option<String> {
+    env::var(format!("AOC_DAY{}_{}", day, name)).ok()
+}
+
+// Print the text if the variable is set to anything
+pub fn print(day: u32, name: &str, text: impl FnOnce() -> String) {
+    if get(day, name).is_some() {
+        print!("{}", text());
+    }
+}
+
+// Write the contents to the file the variable names
+pub fn write(day: u32, name: &str, contents: impl FnOnce() -> String) {
+    if let Some(path) = get(day, name) {
+        fs::write(&path, contents())
+            .unwrap_or_else(|error| panic!("ERROR: failed to write {}, {}", path, error));
+    }
+}
@@ -0,0 +1,240 @@
+// Maps of which body orbits which, as given on day 6. A map may have several roots, but every body
+// orbits at most one other.
+
+use std::collections::HashMap;
+use std::fmt;
+
+// A body interned by an `OrbitTree`, only meaningful for the tree that returned it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BodyId(usize);
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    // The line number and the line that isn't of the form "A)B"
+    Malformed(usize, String),
+    // A body and the two different bodies it is said to orbit
+    MultipleParents(String, String, String),
+    // The bodies in the cycle, each orbiting the next and the last orbiting the first
+    Cycle(Vec<String>),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Malformed(line, text) => write!(f, "line {} is not an orbit: {:?}", line, text),
+            Error::MultipleParents(body, first, second) => {
+                write!(f, "{} orbits both {} and {}", body, first, second)
+            }
+            Error::Cycle(bodies) => write!(f, "orbits form a cycle: {}", bodies.join(" -> ")),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct OrbitTree {
+    names: Vec<String>,
+    ids: HashMap<String, BodyId>,
+    parents: Vec<Option<BodyId>>,
+    // How many bodies each body orbits, directly or indirectly
+    depths: Vec<usize>,
+}
+
+impl OrbitTree {
+    // Parse lines of the form "A)B", meaning B orbits A
+    pub fn parse(input: &str) -> Result<Self, Error> {
+        let mut tree = OrbitTree {
+            names: Vec::new(),
+            ids: HashMap::new(),
+            parents: Vec::new(),
+            depths: Vec::new(),
+        };
+        for (number, line) in input.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (orbited, orbiter) = match line.split_once(')') {
+                Some((orbited, orbiter)) if !orbited.is_empty() && !orbiter.is_empty() => {
+                    (orbited, orbiter)
+                }
+                _ => return Err(Error::Malformed(number + 1, line.to_owned())),
+            };
+            let parent = tree.intern(orbited);
+            let child = tree.intern(orbiter);
+            match tree.parents[child.0] {
+                Some(existing) if existing != parent => {
+                    return Err(Error::MultipleParents(
+                        orbiter.to_owned(),
+                        tree.names[existing.0].clone(),
+                        orbited.to_owned(),
+                    ))
+                }
+                Some(_) => continue,
+                None => {}
+            }
+            tree.parents[child.0] = Some(parent);
+        }
+        tree.depths = tree.compute_depths()?;
+        Ok(tree)
+    }
+
+    fn intern(&mut self, name: &str) -> BodyId {
+        if let Some(&id) = self.ids.get(name) {
+            return id;
+        }
+        let id = BodyId(self.names.len());
+        self.names.push(name.to_owned());
+        self.ids.insert(name.to_owned(), id);
+        self.parents.push(None);
+        id
+    }
+
+    // Work out every depth once, walking up from each body only as far as the first body whose
+    // depth is already known. Running into a body still being walked means there is a cycle.
+    fn compute_depths(&self) -> Result<Vec<usize>, Error> {
+        let mut depths = vec![None; self.names.len()];
+        let mut walking = vec![false; self.names.len()];
+        for start in 0..self.names.len() {
+            let mut stack = Vec::new();
+            let mut current = Some(BodyId(start));
+            let mut depth = 0;
+            while let Some(body) = current {
+                if let Some(known) = depths[body.0] {
+                    depth = known + 1;
+                    break;
+                }
+                if walking[body.0] {
+                    let first = stack.iter().position(|&id| id == body).unwrap();
+                    let cycle = stack[first..]
+                        .iter()
+                        .map(|&id| self.name(id).to_owned())
+                        .collect();
+                    return Err(Error::Cycle(cycle));
+                }
+                walking[body.0] = true;
+                stack.push(body);
+                current = self.parents[body.0];
+            }
+            for body in stack.into_iter().rev() {
+                depths[body.0] = Some(depth);
+                depth += 1;
+            }
+        }
+        Ok(depths.into_iter().map(Option::unwrap).collect())
+    }
+
+    pub fn id(&self, name: &str) -> Option<BodyId> {
+        self.ids.get(name).copied()
+    }
+
+    pub fn name(&self, body: BodyId) -> &str {
+        &self.names[body.0]
+    }
+
+    pub fn parent(&self, body: BodyId) -> Option<BodyId> {
+        self.parents[body.0]
+    }
+
+    pub fn depth(&self, body: BodyId) -> usize {
+        self.depths[body.0]
+    }
+
+    // Every direct and indirect orbit in the map
+    pub fn total_orbits(&self) -> usize {
+        self.depths.iter().sum()
+    }
+
+    // The deepest body that is `a` or orbited by it, and also `b` or orbited by it. Bodies in
+    // separate trees have none.
+    pub fn lowest_common_ancestor(&self, mut a: BodyId, mut b: BodyId) -> Option<BodyId> {
+        while self.depth(a) > self.depth(b) {
+            a = self.parent(a)?;
+        }
+        while self.depth(b) > self.depth(a) {
+            b = self.parent(b)?;
+        }
+        while a != b {
+            a = self.parent(a)?;
+            b = self.parent(b)?;
+        }
+        Some(a)
+    }
+
+    // How many orbits lie between two bodies, going through the lowest body they have in common
+    pub fn distance(&self, a: BodyId, b: BodyId) -> Option<usize> {
+        let common = self.lowest_common_ancestor(a, b)?;
+        Some(self.depth(a) + self.depth(b) - 2 * self.depth(common))
+    }
+
+    // The orbital transfers needed for `from` to end up orbiting the same body as `to`
+    pub fn transfers(&self, from: BodyId, to: BodyId) -> Option<usize> {
+        self.distance(self.parent(from)?, self.parent(to)?)
+    }
+
+    // The map in the graphviz DOT format, with an edge from each body to the bodies orbiting it
+    pub fn to_dot(&self) -> String {
+        let mut dot = "digraph orbits {\n".to_owned();
+        for (child, parent) in self.parents.iter().enumerate() {
+            if let Some(parent) = parent {
+                dot.push_str(&format!(
+                    "    \"{}\" -> \"{}\";\n",
+                    self.names[parent.0], self.names[child]
+                ));
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "COM)B\nB)C\nC)D\nD)E\nE)F\nB)G\nG)H\nD)I\nE)J\nJ)K\nK)L\nK)YOU\nI)SAN";
+
+    #[test]
+    fn depths_and_transfers() {
+        let tree = OrbitTree::parse(EXAMPLE).unwrap();
+        assert_eq!(tree.total_orbits(), 42 + 7 + 5);
+        let l = tree.id("L").unwrap();
+        assert_eq!(tree.depth(l), 7);
+        assert_eq!(tree.parent(l).map(|body| tree.name(body)), Some("K"));
+        let (you, san) = (tree.id("YOU").unwrap(), tree.id("SAN").unwrap());
+        assert_eq!(
+            tree.lowest_common_ancestor(you, san)
+                .map(|body| tree.name(body)),
+            Some("D")
+        );
+        assert_eq!(tree.distance(you, san), Some(6));
+        assert_eq!(tree.transfers(you, san), Some(4));
+        let h = tree.id("H").unwrap();
+        assert_eq!(tree.distance(h, l), Some(8));
+    }
+
+    #[test]
+    fn separate_trees() {
+        let tree = OrbitTree::parse("A)B\nC)D\n").unwrap();
+        let (b, d) = (tree.id("B").unwrap(), tree.id("D").unwrap());
+        assert_eq!(tree.lowest_common_ancestor(b, d), None);
+        assert_eq!(tree.distance(b, d), None);
+        assert_eq!(
+            tree.to_dot(),
+            "digraph orbits {\n    \"A\" -> \"B\";\n    \"C\" -> \"D\";\n}\n"
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            OrbitTree::parse("A)B\nB-C").unwrap_err(),
+            Error::Malformed(2, "B-C".to_owned())
+        );
+        let error = OrbitTree::parse("A)B\nC)B").unwrap_err();
+        assert_eq!(error.to_string(), "B orbits both A and C");
+        assert_eq!(
+            OrbitTree::parse("COM)A\nC)A2\nA2)B\nB)C").unwrap_err(),
+            Error::Cycle(vec!["C".to_owned(), "B".to_owned(), "A2".to_owned()])
+        );
+    }
+}
@@ -0,0 +1,231 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+use std::hash::Hash;
+use std::marker::PhantomData;
+
+pub trait Graph {
+    type Node: Clone + Eq + Hash;
+
+    // Every node reachable in one move from `node`, along with the cost of getting there
+    fn neighbors(&self, node: &Self::Node) -> Vec<(Self::Node, usize)>;
+}
+
+// A graph whose edges are given by a closure
+pub struct FnGraph<N, F> {
+    neighbors: F,
+    node: PhantomData<N>,
+}
+
+impl<N, F: Fn(&N) -> Vec<(N, usize)>> FnGraph<N, F> {
+    pub fn new(neighbors: F) -> Self {
+        FnGraph {
+            neighbors,
+            node: PhantomData,
+        }
+    }
+}
+
+impl<N, F> Graph for FnGraph<N, F>
+where
+    N: Clone + Eq + Hash,
+    F: Fn(&N) -> Vec<(N, usize)>,
+{
+    type Node = N;
+
+    fn neighbors(&self, node: &N) -> Vec<(N, usize)> {
+        (self.neighbors)(node)
+    }
+}
+
+// The distance to every node a search reached, which node it was reached from, and the goal the
+// search stopped at if any
+pub struct Distances<N> {
+    distances: HashMap<N, usize>,
+    parents: HashMap<N, N>,
+    goal: Option<N>,
+}
+
+impl<N: Clone + Eq + Hash> Distances<N> {
+    fn new(start: N) -> Self {
+        let mut distances = HashMap::new();
+        distances.insert(start, 0);
+        Distances {
+            distances,
+            parents: HashMap::new(),
+            goal: None,
+        }
+    }
+
+    pub fn goal(&self) -> Option<&N> {
+        self.goal.as_ref()
+    }
+
+    // The distance to the goal, if one was reached
+    pub fn goal_distance(&self) -> Option<usize> {
+        self.goal().and_then(|goal| self.distance(goal))
+    }
+
+    pub fn distance(&self, node: &N) -> Option<usize> {
+        self.distances.get(node).copied()
+    }
+
+    // The nodes along a shortest path from the start to `node`, including both ends
+    pub fn path_to(&self, node: &N) -> Option<Vec<N>> {
+        if !self.distances.contains_key(node) {
+            return None;
+        }
+        let mut path = vec![node.clone()];
+        while let Some(parent) = self.parents.get(path.last().unwrap()) {
+            path.push(parent.clone());
+        }
+        path.reverse();
+        Some(path)
+    }
+}
+
+// Breadth first search from `start`, treating every move as costing 1 and stopping early if a node
+// satisfying `is_goal` is reached
+pub fn bfs<G: Graph>(
+    graph: &G,
+    start: G::Node,
+    mut is_goal: impl FnMut(&G::Node) -> bool,
+) -> Distances<G::Node> {
+    let mut result = Distances::new(start.clone());
+    let mut queue = VecDeque::new();
+    queue.push_back(start);
+    while let Some(current) = queue.pop_front() {
+        if is_goal(&current) {
+            result.goal = Some(current);
+            break;
+        }
+        let distance = result.distances[&current] + 1;
+        for (neighbor, _) in graph.neighbors(&current) {
+            if !result.distances.contains_key(&neighbor) {
+                result.distances.insert(neighbor.clone(), distance);
+                result.parents.insert(neighbor.clone(), current.clone());
+                queue.push_back(neighbor);
+            }
+        }
+    }
+    result
+}
+
+struct Entry<N> {
+    // The cost so far plus the heuristic estimate of the cost remaining
+    priority: usize,
+    node: N,
+}
+
+// Note that this is "backwards" turning the BinaryHeap into a min queue instead of a max queue
+impl<N> Ord for Entry<N> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.priority.cmp(&self.priority)
+    }
+}
+
+impl<N> PartialOrd for Entry<N> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<N> PartialEq for Entry<N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.priority == other.priority
+    }
+}
+
+impl<N> Eq for Entry<N> {}
+
+// A* from `start`, stopping once a node satisfying `is_goal` is reached. `heuristic` must never
+// overestimate the remaining cost to a goal.
+pub fn astar<G: Graph>(
+    graph: &G,
+    start: G::Node,
+    mut is_goal: impl FnMut(&G::Node) -> bool,
+    heuristic: impl Fn(&G::Node) -> usize,
+) -> Distances<G::Node> {
+    let mut result = Distances::new(start.clone());
+    let mut pqueue = BinaryHeap::new();
+    pqueue.push(Entry {
+        priority: heuristic(&start),
+        node: start,
+    });
+    while let Some(Entry { priority, node }) = pqueue.pop() {
+        let distance = result.distances[&node];
+        // Skip entries for nodes that have since been reached more cheaply
+        if priority > distance + heuristic(&node) {
+            continue;
+        }
+        if is_goal(&node) {
+            result.goal = Some(node);
+            break;
+        }
+        for (neighbor, cost) in graph.neighbors(&node) {
+            let new_distance = distance + cost;
+            if result
+                .distances
+                .get(&neighbor)
+                .is_none_or(|&old| new_distance < old)
+            {
+                result.distances.insert(neighbor.clone(), new_distance);
+                result.parents.insert(neighbor.clone(), node.clone());
+                pqueue.push(Entry {
+                    priority: new_distance + heuristic(&neighbor),
+                    node: neighbor,
+                });
+            }
+        }
+    }
+    result
+}
+
+// Dijkstra's from `start`, stopping once a node satisfying `is_goal` is reached
+pub fn dijkstra<G: Graph>(
+    graph: &G,
+    start: G::Node,
+    is_goal: impl FnMut(&G::Node) -> bool,
+) -> Distances<G::Node> {
+    astar(graph, start, is_goal, |_| 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A line of nodes 0..10 with a slow direct edge from 0 to 9
+    fn line() -> impl Graph<Node = usize> {
+        FnGraph::new(|&node: &usize| {
+            let mut neighbors = Vec::new();
+            if node > 0 {
+                neighbors.push((node - 1, 1));
+            }
+            if node < 9 {
+                neighbors.push((node + 1, 1));
+            }
+            if node == 0 {
+                neighbors.push((9, 20));
+            }
+            neighbors
+        })
+    }
+
+    #[test]
+    fn bfs_counts_moves() {
+        let result = bfs(&line(), 0, |_| false);
+        assert_eq!(result.distance(&9), Some(1));
+        assert_eq!(result.distance(&5), Some(5));
+        assert_eq!(result.path_to(&7), Some(vec![0, 9, 8, 7]));
+    }
+
+    #[test]
+    fn dijkstra_and_astar_weigh_moves() {
+        let result = dijkstra(&line(), 0, |&node| node == 9);
+        assert_eq!(result.goal(), Some(&9));
+        assert_eq!(result.goal_distance(), Some(9));
+        assert_eq!(result.path_to(&9), Some((0..10).collect()));
+        let result = astar(&line(), 0, |&node| node == 9, |&node| 9 - node);
+        assert_eq!(result.distance(&9), Some(9));
+        assert_eq!(result.path_to(&12), None);
+    }
+}
@@ -0,0 +1,188 @@
+// The Space Image Format the elves use to send pictures, as described on day 8
+
+use crate::grid::Grid;
+use std::fmt;
+
+pub const BLACK: u8 = 0;
+pub const WHITE: u8 = 1;
+pub const TRANSPARENT: u8 = 2;
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    // Zero pixels wide or tall
+    NoSize,
+    Empty,
+    // A character other than a digit, and its position in the input
+    InvalidDigit(usize, char),
+    // The number of pixels left over after the last complete layer
+    IncompleteLayer(usize),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::NoSize => write!(f, "images must be at least one pixel wide and tall"),
+            Error::Empty => write!(f, "no layers in the image"),
+            Error::InvalidDigit(position, ch) => {
+                write!(f, "{:?} at position {} is not a digit", ch, position)
+            }
+            Error::IncompleteLayer(pixels) => {
+                write!(f, "the last layer is missing pixels, only {} given", pixels)
+            }
+        }
+    }
+}
+
+// How often each digit appears in a layer
+#[derive(Debug, PartialEq)]
+pub struct LayerStats {
+    counts: [usize; 10],
+}
+
+impl LayerStats {
+    pub fn count(&self, digit: u8) -> usize {
+        self.counts[digit as usize]
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SifImage {
+    pub width: usize,
+    pub height: usize,
+    // The first layer is in front of all the others
+    pub layers: Vec<Grid<u8>>,
+}
+
+impl SifImage {
+    pub fn parse(input: &str, width: usize, height: usize) -> Result<Self, Error> {
+        if width == 0 || height == 0 {
+            return Err(Error::NoSize);
+        }
+        let pixels = input
+            .trim_end()
+            .chars()
+            .enumerate()
+            .map(|(position, ch)| match ch.to_digit(10) {
+                Some(digit) => Ok(digit as u8),
+                None => Err(Error::InvalidDigit(position, ch)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if pixels.is_empty() {
+            return Err(Error::Empty);
+        }
+        let layer_size = width * height;
+        if pixels.len() % layer_size != 0 {
+            return Err(Error::IncompleteLayer(pixels.len() % layer_size));
+        }
+        let layers = pixels
+            .chunks(layer_size)
+            .map(|layer| Grid::from_cells(width, layer.to_vec()))
+            .collect();
+        Ok(SifImage {
+            width,
+            height,
+            layers,
+        })
+    }
+
+    // The digit stream the image was parsed from. Day 8 never sends an image back, so this is only
+    // used to check parsing round trips.
+    #[allow(dead_code)]
+    pub fn encode(&self) -> String {
+        self.layers
+            .iter()
+            .flat_map(|layer| layer.iter().map(|(_, &pixel)| (b'0' + pixel) as char))
+            .collect()
+    }
+
+    pub fn stats(&self) -> Vec<LayerStats> {
+        self.layers
+            .iter()
+            .map(|layer| {
+                let mut counts = [0; 10];
+                for (_, &pixel) in layer.iter() {
+                    counts[pixel as usize] += 1;
+                }
+                LayerStats { counts }
+            })
+            .collect()
+    }
+
+    // Stack the layers, each pixel showing the first one that isn't transparent. Pixels that are
+    // transparent in every layer stay transparent.
+    pub fn composite(&self) -> Grid<u8> {
+        self.layers.iter().rev().fold(
+            Grid::new(self.width, self.height, TRANSPARENT),
+            |mut acc, layer| {
+                for (point, &pixel) in layer.iter() {
+                    if pixel != TRANSPARENT {
+                        acc[point] = pixel
+                    }
+                }
+                acc
+            },
+        )
+    }
+
+    // The composited image as a plain PGM, with anything still transparent in grey
+    pub fn to_pgm(&self) -> String {
+        let image = self.composite();
+        let mut out = format!("P2\n{} {}\n255\n", self.width, self.height);
+        for y in 0..self.height {
+            let row = image
+                .row(y)
+                .iter()
+                .map(|&pixel| match pixel {
+                    BLACK => "0",
+                    WHITE => "255",
+                    _ => "128",
+                })
+                .collect::<Vec<_>>();
+            out += &row.join(" ");
+            out.push('\n');
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_encode() {
+        let image = SifImage::parse("123456789012\n", 3, 2).unwrap();
+        assert_eq!(image.layers.len(), 2);
+        assert_eq!(image.layers[1].row(0), [7, 8, 9]);
+        assert_eq!(image.encode(), "123456789012");
+        let stats = image.stats();
+        assert_eq!((stats[1].count(0), stats[1].count(1)), (1, 1));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(SifImage::parse("1234", 0, 2), Err(Error::NoSize));
+        assert_eq!(SifImage::parse("", 2, 2), Err(Error::Empty));
+        assert_eq!(
+            SifImage::parse("12a4", 2, 2),
+            Err(Error::InvalidDigit(2, 'a'))
+        );
+        assert_eq!(
+            SifImage::parse("123456", 2, 2),
+            Err(Error::IncompleteLayer(2))
+        );
+        assert_eq!(
+            Error::InvalidDigit(2, 'a').to_string(),
+            "'a' at position 2 is not a digit"
+        );
+    }
+
+    #[test]
+    fn composite() {
+        let image = SifImage::parse("0222112222120000", 2, 2).unwrap();
+        assert_eq!(image.composite(), Grid::from_cells(2, vec![0, 1, 1, 0]));
+        assert_eq!(image.to_pgm(), "P2\n2 2\n255\n0 255\n255 0\n");
+        let image = SifImage::parse("2220", 2, 1).unwrap();
+        assert_eq!(image.to_pgm(), "P2\n2 1\n255\n128 0\n");
+    }
+}
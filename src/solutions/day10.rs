@@ -1,22 +1,29 @@
+use crate::grid::Grid;
 use aoc_runner_derive::{aoc, aoc_generator};
-use std::collections::BTreeMap;
-use std::collections::HashSet;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, VecDeque};
 
 #[aoc_generator(day10)]
-pub fn parse_day10(input: &str) -> Vec<(i32, i32)> {
-    input
-        .lines()
-        .enumerate()
-        .flat_map(|(y, line)| {
-            line.chars().enumerate().filter_map(move |(x, loc)| {
-                if loc == '#' {
-                    Some((x as i32, y as i32))
-                } else {
-                    None
-                }
-            })
-        })
-        .collect()
+pub fn parse_day10(input: &str) -> AsteroidField {
+    let asteroids = Grid::parse(input, |loc| loc == '#')
+        .iter()
+        .filter(|(_, roid)| **roid)
+        .map(|((x, y), _)| (x as i32, y as i32))
+        .collect();
+    let mut field = AsteroidField {
+        asteroids,
+        best: None,
+    };
+    field.best = best_station(&field);
+    field
+}
+
+type Point = (i32, i32);
+
+pub struct AsteroidField {
+    asteroids: Vec<Point>,
+    // The best station, worked out once for both parts
+    best: Option<(Point, usize)>,
 }
 
 fn gcd(a: i32, b: i32) -> i32 {
@@ -56,75 +63,154 @@ impl Slope {
     }
 }
 
-#[aoc(day10, part1)]
-pub fn solve_day10_part1(input: &[(i32, i32)]) -> usize {
-    input
-        .iter()
-        .map(|(x, y)| {
-            let mut visible = HashSet::new();
-            input
-                .iter()
-                .filter(|(roidx, roidy)| visible.insert(Slope::new(y - roidy, x - roidx)))
-                .count()
-        })
-        .max()
-        .unwrap()
-        - 1
+// Ordered by the angle clockwise from straight up, exactly. The slopes pointing right or straight
+// up come before those pointing left or straight down, and within each half the cross product
+// tells which is further clockwise.
+impl Ord for Slope {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Pointing from the station towards the asteroid, with y going up
+        let direction = |slope: &Slope| (-slope.run, slope.rise);
+        let half = |(x, y): (i32, i32)| if x > 0 || (x == 0 && y > 0) { 0 } else { 1 };
+        let (a, b) = (direction(self), direction(other));
+        half(a)
+            .cmp(&half(b))
+            .then_with(|| (a.0 * b.1).cmp(&(a.1 * b.0)))
+    }
 }
 
-// Solution coordinates from part 1
-const STATION_X: i32 = 23;
-const STATION_Y: i32 = 29;
+impl PartialOrd for Slope {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
 
-#[aoc(day10, part2)]
-pub fn solve_day10_part2(input: &[(i32, i32)]) -> i32 {
-    let roids = input
-        .iter()
-        .filter(|roid| **roid != (STATION_X, STATION_Y))
-        .map(|(roidx, roidy)| {
-            (
-                ((((STATION_Y - roidy) as f64).atan2((STATION_X - roidx) as f64)
-                    - std::f64::consts::FRAC_PI_2)
-                    .rem_euclid(std::f64::consts::PI * 2.0)
-                    * 1_000_000_000_000.0) as i64,
-                (roidx, roidy),
-            )
-        })
-        .collect::<Vec<_>>();
+fn distance_squared(from: Point, to: Point) -> i32 {
+    (to.0 - from.0).pow(2) + (to.1 - from.1).pow(2)
+}
 
-    let mut roidmap = BTreeMap::new();
-    for (angle, roid) in roids {
-        roidmap
-            .entry(angle)
-            .and_modify(|roidlist: &mut Vec<_>| roidlist.push(roid))
-            .or_insert_with(|| vec![roid]);
-    }
-
-    let mut count = 1;
-    loop {
-        for (_, roidlist) in roidmap.iter_mut() {
-            if let Some((idx, roid)) =
-                roidlist
-                    .iter()
-                    .enumerate()
-                    .min_by_key(|(_, (roidx, roidy))| {
-                        (**roidx - STATION_X).pow(2) + (**roidy - STATION_Y).pow(2)
-                    })
-            {
-                if count == 200 {
-                    return 100 * roid.0 + roid.1;
-                }
-                roidlist.remove(idx);
-                count += 1;
-            }
+// The asteroids a laser vaporizes as it turns, see `AsteroidField::vaporize`
+pub struct Vaporization {
+    // The asteroids left in each direction still holding any, clockwise and nearest first
+    lines: Vec<VecDeque<Point>>,
+    // The direction the laser points next
+    next: usize,
+}
+
+impl Iterator for Vaporization {
+    type Item = Point;
+
+    fn next(&mut self) -> Option<Point> {
+        if self.lines.is_empty() {
+            return None;
+        }
+        let roid = self.lines[self.next].pop_front();
+        if self.lines[self.next].is_empty() {
+            self.lines.remove(self.next);
+        } else {
+            self.next += 1;
+        }
+        if self.next == self.lines.len() {
+            self.next = 0;
+        }
+        roid
+    }
+}
+
+impl AsteroidField {
+    // Every other asteroid lined up behind one another as seen from `station`, the nearest of each
+    // line first
+    fn lines_of_sight(&self, station: Point) -> BTreeMap<Slope, Vec<Point>> {
+        let mut lines = BTreeMap::new();
+        for &roid in self.asteroids.iter().filter(|&&roid| roid != station) {
+            lines
+                .entry(Slope::new(station.1 - roid.1, station.0 - roid.0))
+                .or_insert_with(Vec::new)
+                .push(roid);
+        }
+        for line in lines.values_mut() {
+            line.sort_by_key(|&roid| distance_squared(station, roid));
+        }
+        lines
+    }
+
+    // The asteroids that can be seen from `station`, which need not be an asteroid itself
+    pub fn visible_from(&self, station: Point) -> Vec<Point> {
+        self.lines_of_sight(station)
+            .values()
+            .map(|line| line[0])
+            .collect()
+    }
+
+    // Every other asteroid in the order a laser at `station` vaporizes them. It starts pointing up
+    // and turns clockwise, vaporizing the nearest asteroid in each direction it passes.
+    pub fn vaporize(&self, station: Point) -> Vaporization {
+        Vaporization {
+            lines: self
+                .lines_of_sight(station)
+                .into_values()
+                .map(VecDeque::from)
+                .collect(),
+            next: 0,
         }
     }
+
+    // The whole of `vaporize` at once
+    pub fn vaporization_order(&self, station: Point) -> Vec<Point> {
+        self.vaporize(station).collect()
+    }
+}
+
+// The asteroid that can see the most other asteroids, and how many it sees
+pub fn best_station(field: &AsteroidField) -> Option<(Point, usize)> {
+    field
+        .asteroids
+        .iter()
+        .map(|&roid| (roid, field.visible_from(roid).len()))
+        .max_by_key(|&(_, visible)| visible)
+}
+
+#[aoc(day10, part1)]
+pub fn solve_day10_part1(field: &AsteroidField) -> usize {
+    let (_, visible) = field.best.expect("ERROR: there are no asteroids");
+    visible
+}
+
+// The 200th asteroid vaporized from the best station
+#[aoc(day10, part2)]
+pub fn solve_day10_part2(field: &AsteroidField) -> i32 {
+    let (station, _) = field.best.expect("ERROR: there are no asteroids");
+    let roid = *field
+        .vaporization_order(station)
+        .get(199)
+        .expect("ERROR: fewer than 200 asteroids to vaporize");
+    100 * roid.0 + roid.1
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    const LARGE: &str = ".#..##.###...#######\n\
+                         ##.############..##.\n\
+                         .#.######.########.#\n\
+                         .###.#######.####.#.\n\
+                         #####.##.#.##.###.##\n\
+                         ..#####..#.#########\n\
+                         ####################\n\
+                         #.####....###.#.#.##\n\
+                         ##.#################\n\
+                         #####.##.###..####..\n\
+                         ..######..##.#######\n\
+                         ####.##.####...##..#\n\
+                         .#####..#.######.###\n\
+                         ##...#.##########...\n\
+                         #.##########.#######\n\
+                         .####.#.###.###.#.##\n\
+                         ....##.##.###..#####\n\
+                         .#.#.###########.###\n\
+                         #.#.#.#####.####.###\n\
+                         ###.##.####.##.#..##";
+
     #[test]
     fn gcd_test() {
         assert_eq!(gcd(6, 9), 3);
@@ -178,26 +264,61 @@ mod tests {
 
     #[test]
     fn day10_example4() {
-        const INPUT: &str = ".#..##.###...#######\n\
-                             ##.############..##.\n\
-                             .#.######.########.#\n\
-                             .###.#######.####.#.\n\
-                             #####.##.#.##.###.##\n\
-                             ..#####..#.#########\n\
-                             ####################\n\
-                             #.####....###.#.#.##\n\
-                             ##.#################\n\
-                             #####.##.###..####..\n\
-                             ..######..##.#######\n\
-                             ####.##.####...##..#\n\
-                             .#####..#.######.###\n\
-                             ##...#.##########...\n\
-                             #.##########.#######\n\
-                             .####.#.###.###.#.##\n\
-                             ....##.##.###..#####\n\
-                             .#.#.###########.###\n\
-                             #.#.#.#####.####.###\n\
-                             ###.##.####.##.#..##";
-        assert_eq!(solve_day10_part1(&parse_day10(INPUT)), 210);
+        assert_eq!(solve_day10_part1(&parse_day10(LARGE)), 210);
+    }
+
+    #[test]
+    fn day10_small_vaporization() {
+        const INPUT: &str = ".#....#####...#..\n\
+                             ##...##.#####..##\n\
+                             ##...#...#.#####.\n\
+                             ..#.....#...###..\n\
+                             ..#.#.....#....##";
+        let field = parse_day10(INPUT);
+        let station = (8, 3);
+        assert_eq!(field.visible_from(station).len(), 30);
+        let order = field.vaporization_order(station);
+        assert_eq!(order.len(), field.asteroids.len() - 1);
+        assert_eq!(order[..4], [(8, 1), (9, 0), (9, 1), (10, 0)]);
+        assert_eq!(order[30..33], [(8, 0), (10, 1), (14, 0)]);
+        assert_eq!(order.last(), Some(&(14, 3)));
+    }
+
+    #[test]
+    fn slope_order() {
+        // Offsets on the map, clockwise from straight up
+        let slopes = [
+            (0, -1),
+            (1, -3),
+            (2, -1),
+            (1, 0),
+            (3, 1),
+            (0, 1),
+            (-1, 1),
+            (-1, 0),
+            (-1, -3),
+        ]
+        .iter()
+        .map(|&(x, y): &(i32, i32)| Slope::new(-y, -x))
+        .collect::<Vec<_>>();
+        for pair in slopes.windows(2) {
+            assert_eq!(pair[0].cmp(&pair[1]), Ordering::Less);
+        }
+        assert_eq!(Slope::new(4, -2).cmp(&Slope::new(2, -1)), Ordering::Equal);
+    }
+
+    #[test]
+    fn day10_large_vaporization() {
+        let field = parse_day10(LARGE);
+        let station = (11, 13);
+        assert_eq!(field.best, Some((station, 210)));
+        let order = field.vaporize(station).collect::<Vec<_>>();
+        let nth = |n: usize| order[n - 1];
+        assert_eq!((nth(1), nth(2), nth(3)), ((11, 12), (12, 1), (12, 2)));
+        assert_eq!((nth(10), nth(20), nth(50)), ((12, 8), (16, 0), (16, 9)));
+        assert_eq!((nth(100), nth(199), nth(200)), ((10, 16), (9, 6), (8, 2)));
+        assert_eq!((nth(201), nth(299)), ((10, 9), (11, 1)));
+        assert_eq!(order.len(), 299);
+        assert_eq!(solve_day10_part2(&field), 802);
     }
 }
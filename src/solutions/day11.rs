@@ -1,9 +1,11 @@
-use crate::intcode;
+use crate::grid::{Grid, SparseGrid};
+use crate::intcode::{Machine, Status};
+use crate::ocr;
+use crate::options;
 use aoc_runner_derive::{aoc, aoc_generator};
-use std::collections::HashMap;
-use std::sync::mpsc::sync_channel;
-use std::sync::mpsc::TryRecvError;
-use std::thread;
+use std::collections::{HashSet, VecDeque};
+use std::fs;
+use std::path::Path;
 
 #[aoc_generator(day11)]
 pub fn parse_day11(input: &str) -> Vec<i64> {
@@ -13,6 +15,10 @@ pub fn parse_day11(input: &str) -> Vec<i64> {
         .collect()
 }
 
+const BLACK: i64 = 0;
+const WHITE: i64 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 enum Rotation {
     Up,
     Down,
@@ -20,150 +26,200 @@ enum Rotation {
     Right,
 }
 
-#[aoc(day11, part1)]
-pub fn solve_day11_part1(program: &[i64]) -> usize {
-    // Start the brain of the robot
-    let (tx_in, rx_in) = sync_channel(0);
-    let (tx_out, rx_out) = sync_channel(0);
-    let (tx_req, rx_req) = sync_channel(0);
-    let program = program.to_vec();
-    thread::spawn(move || intcode::execute_threaded(program, rx_in, tx_out, Some(&tx_req)));
-
-    let mut bot_x = 0;
-    let mut bot_y = 0;
-    let mut bot_facing = Rotation::Up;
-    let mut hull = HashMap::new();
-    loop {
-        match rx_req.try_recv() {
-            Ok(()) => tx_in
-                .send(*hull.get(&(bot_x, bot_y)).unwrap_or(&0))
-                .expect("ERROR: failed to send color"),
-            Err(TryRecvError::Empty) => (),
-            Err(TryRecvError::Disconnected) => return hull.len(),
+impl Rotation {
+    // Turn left for 0 and right for 1, as the brain outputs them
+    fn turn(self, direction: i64) -> Self {
+        match (direction, self) {
+            (0, Rotation::Up) | (1, Rotation::Down) => Rotation::Left,
+            (0, Rotation::Down) | (1, Rotation::Up) => Rotation::Right,
+            (0, Rotation::Left) | (1, Rotation::Right) => Rotation::Down,
+            (0, Rotation::Right) | (1, Rotation::Left) => Rotation::Up,
+            _ => panic!("ERROR: invalid rotation"),
         }
-        if let Ok(color) = rx_out.try_recv() {
-            hull.insert((bot_x, bot_y), color);
-            let rotation = rx_out.recv().expect("ERROR: failed to recieve rotation");
-            // Rotate according to brain output
-            bot_facing = match (rotation, bot_facing) {
-                (0, Rotation::Up) => Rotation::Left,
-                (0, Rotation::Down) => Rotation::Right,
-                (0, Rotation::Left) => Rotation::Down,
-                (0, Rotation::Right) => Rotation::Up,
-                (1, Rotation::Up) => Rotation::Right,
-                (1, Rotation::Down) => Rotation::Left,
-                (1, Rotation::Left) => Rotation::Up,
-                (1, Rotation::Right) => Rotation::Down,
-                _ => panic!("ERROR: invalid rotation"),
-            };
-            // Now move 1 forward
-            match bot_facing {
-                Rotation::Up => bot_y += 1,
-                Rotation::Down => bot_y -= 1,
-                Rotation::Left => bot_x -= 1,
-                Rotation::Right => bot_x += 1,
-            };
+    }
+
+    fn delta(self) -> (i64, i64) {
+        match self {
+            Rotation::Up => (0, -1),
+            Rotation::Down => (0, 1),
+            Rotation::Left => (-1, 0),
+            Rotation::Right => (1, 0),
         }
     }
-}
 
-#[aoc(day11, part2)]
-pub fn solve_day11_part2(program: &[i64]) -> String {
-    // Start the brain of the robot
-    let (tx_in, rx_in) = sync_channel(0);
-    let (tx_out, rx_out) = sync_channel(0);
-    let (tx_req, rx_req) = sync_channel(0);
-    let program = program.to_vec();
-    thread::spawn(move || intcode::execute_threaded(program, rx_in, tx_out, Some(&tx_req)));
-
-    let mut first = true;
-    let mut bot_x = 0_i32;
-    let mut bot_y = 0;
-    let mut bot_facing = Rotation::Up;
-    let mut hull = HashMap::new();
-    loop {
-        match rx_req.try_recv() {
-            Ok(()) => tx_in
-                .send(*hull.get(&(bot_x, bot_y)).unwrap_or(if first {
-                    first = false;
-                    &1
-                } else {
-                    &0
-                }))
-                .expect("ERROR: failed to send color"),
-            Err(TryRecvError::Empty) => (),
-            Err(TryRecvError::Disconnected) => break,
+    fn symbol(self) -> char {
+        match self {
+            Rotation::Up => '^',
+            Rotation::Down => 'v',
+            Rotation::Left => '<',
+            Rotation::Right => '>',
         }
-        if let Ok(color) = rx_out.try_recv() {
-            hull.insert((bot_x, bot_y), color);
-            let rotation = rx_out.recv().expect("ERROR: failed to recieve rotation");
-            // Rotate according to brain output
-            bot_facing = match (rotation, bot_facing) {
-                (0, Rotation::Up) => Rotation::Left,
-                (0, Rotation::Down) => Rotation::Right,
-                (0, Rotation::Left) => Rotation::Down,
-                (0, Rotation::Right) => Rotation::Up,
-                (1, Rotation::Up) => Rotation::Right,
-                (1, Rotation::Down) => Rotation::Left,
-                (1, Rotation::Left) => Rotation::Up,
-                (1, Rotation::Right) => Rotation::Down,
-                _ => panic!("ERROR: invalid rotation"),
-            };
-            // Now move 1 forward
-            match bot_facing {
-                Rotation::Up => bot_y += 1,
-                Rotation::Down => bot_y -= 1,
-                Rotation::Left => bot_x -= 1,
-                Rotation::Right => bot_x += 1,
-            };
+    }
+}
+
+// One panel painted by the robot, and where it went afterwards
+#[derive(Clone, Debug, PartialEq)]
+struct Paint {
+    panel: (i64, i64),
+    color: i64,
+    position: (i64, i64),
+    facing: Rotation,
+}
+
+const MAX_STEPS: usize = 1_000_000;
+
+struct HullRobot {
+    brain: Machine,
+    position: (i64, i64),
+    facing: Rotation,
+    hull: SparseGrid<i64>,
+}
+
+impl HullRobot {
+    // A robot standing on a panel of `start_color`, with every other panel black
+    fn new(program: &[i64], start_color: i64) -> Self {
+        let mut hull = SparseGrid::new();
+        hull.insert((0, 0), start_color);
+        HullRobot {
+            brain: Machine::new(program),
+            position: (0, 0),
+            facing: Rotation::Up,
+            hull,
         }
     }
-    let off_x = 0 - *hull
-        .iter()
-        .min_by_key(|((x, _), _)| x)
-        .map(|((x, _), _)| x)
-        .unwrap();
-    let off_y = 0 - *hull
-        .iter()
-        .min_by_key(|((_, y), _)| y)
-        .map(|((_, y), _)| y)
-        .unwrap();
-
-    let max_x = 1
-        + off_x
-        + *hull
-            .iter()
-            .max_by_key(|((x, _), _)| x)
-            .map(|((x, _), _)| x)
-            .unwrap();
-    let max_y = 1
-        + off_y
-        + *hull
-            .iter()
-            .max_by_key(|((_, y), _)| y)
-            .map(|((_, y), _)| y)
-            .unwrap();
-
-    let mut hull_paintjob = vec![vec![0; max_x as usize]; max_y as usize];
-
-    for ((x, y), color) in hull.iter() {
-        hull_paintjob[(*y + off_y) as usize][(*x + off_x) as usize] = *color;
+}
+
+// Each step the robot paints a panel, turns and moves forward one panel
+impl Iterator for HullRobot {
+    type Item = Paint;
+
+    fn next(&mut self) -> Option<Paint> {
+        let color = *self.hull.get(self.position).unwrap_or(&BLACK);
+        let mut output = Vec::new();
+        let status = self
+            .brain
+            .run(&mut VecDeque::from(vec![color]), &mut output, MAX_STEPS);
+        let (color, direction) = match (status, &output[..]) {
+            (Status::Halted, []) => return None,
+            (Status::NeedsInput, &[color, direction]) | (Status::Halted, &[color, direction]) => {
+                (color, direction)
+            }
+            _ => panic!("ERROR: unexpected output from the brain {:?}", output),
+        };
+        let panel = self.position;
+        self.hull.insert(panel, color);
+        self.facing = self.facing.turn(direction);
+        let (dx, dy) = self.facing.delta();
+        self.position = (panel.0 + dx, panel.1 + dy);
+        Some(Paint {
+            panel,
+            color,
+            position: self.position,
+            facing: self.facing,
+        })
     }
+}
+
+fn render_hull(hull: &SparseGrid<i64>) -> String {
+    hull.render(' ', |_, &color| if color == WHITE { '█' } else { ' ' })
+}
+
+// The hull as a plain PBM image, where 1 is black
+fn hull_pbm(hull: &SparseGrid<i64>) -> String {
+    let text = render_hull(hull);
+    let lines = text.lines().collect::<Vec<_>>();
+    let width = lines.first().map_or(0, |line| line.chars().count());
+    let mut out = format!("P1\n{} {}\n", width, lines.len());
+    for line in lines {
+        let row = line
+            .chars()
+            .map(|ch| if ch == '█' { "0" } else { "1" })
+            .collect::<Vec<_>>();
+        out += &row.join(" ");
+        out.push('\n');
+    }
+    out
+}
 
-    hull_paintjob
+// The hull after each paint with the robot drawn on it, all framed the same way
+fn animate(start_color: i64, log: &[Paint]) -> Vec<String> {
+    let points = log
         .iter()
-        .rev()
-        .map(|line| {
-            line.iter()
-                .map(|color| match color {
-                    1 => '█',
-                    _ => ' ',
-                })
-                .collect::<String>()
-        })
-        .fold("\n".to_owned(), |mut acc, row| {
-            acc.push_str(&row);
-            acc.push('\n');
-            acc
+        .flat_map(|paint| vec![paint.panel, paint.position])
+        .chain(Some((0, 0)));
+    let (min_x, min_y, max_x, max_y) = points.fold(
+        (i64::MAX, i64::MAX, i64::MIN, i64::MIN),
+        |(min_x, min_y, max_x, max_y), (x, y)| {
+            (min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y))
+        },
+    );
+    let to_grid = |(x, y): (i64, i64)| ((x - min_x) as usize, (y - min_y) as usize);
+    let width = (max_x - min_x + 1) as usize;
+    let height = (max_y - min_y + 1) as usize;
+    let mut panels = Grid::new(width, height, BLACK);
+    panels[to_grid((0, 0))] = start_color;
+    log.iter()
+        .map(|paint| {
+            panels[to_grid(paint.panel)] = paint.color;
+            let robot = to_grid(paint.position);
+            panels.render(|point, &color| {
+                if point == robot {
+                    paint.facing.symbol()
+                } else if color == WHITE {
+                    '█'
+                } else {
+                    ' '
+                }
+            })
         })
+        .collect()
+}
+
+#[aoc(day11, part1)]
+pub fn solve_day11_part1(program: &[i64]) -> usize {
+    HullRobot::new(program, BLACK)
+        .map(|paint| paint.panel)
+        .collect::<HashSet<_>>()
+        .len()
+}
+
+#[aoc(day11, part2)]
+pub fn solve_day11_part2(program: &[i64]) -> String {
+    let mut robot = HullRobot::new(program, WHITE);
+    let log = robot.by_ref().collect::<Vec<_>>();
+    options::print(11, "HULL", || render_hull(&robot.hull));
+    options::write(11, "PBM", || hull_pbm(&robot.hull));
+    if let Some(dir) = options::get(11, "FRAMES") {
+        fs::create_dir_all(&dir).expect("ERROR: failed to create frame directory");
+        for (step, frame) in animate(WHITE, &log).iter().enumerate() {
+            let path = Path::new(&dir).join(format!("step_{:04}.txt", step));
+            fs::write(path, frame).expect("ERROR: failed to write frame");
+        }
+    }
+    let pixels = Grid::parse(&render_hull(&robot.hull), |ch| ch == '█');
+    ocr::read(&pixels).unwrap_or_else(|error| {
+        panic!(
+            "ERROR: failed to read the registration identifier\n{}",
+            error
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn day11_paint_log() {
+        // Paint the panel under the robot white and turn left, twice
+        let program = [3, 100, 104, 1, 104, 0, 3, 100, 104, 1, 104, 0, 99];
+        let mut robot = HullRobot::new(&program, BLACK);
+        let log = robot.by_ref().collect::<Vec<_>>();
+        assert_eq!(log.len(), 2);
+        assert_eq!(log[1].panel, (-1, 0));
+        assert_eq!((log[1].position, log[1].facing), ((-1, 1), Rotation::Down));
+        assert_eq!(render_hull(&robot.hull), "██\n");
+        assert_eq!(hull_pbm(&robot.hull), "P1\n2 1\n0 0\n");
+        assert_eq!(animate(BLACK, &log), ["<█\n  \n", "██\nv \n"]);
+    }
 }
@@ -1,9 +1,11 @@
 extern crate pancurses;
-use crate::intcode;
+use crate::grid::SparseGrid;
+use crate::intcode::{Machine, Status};
+use crate::options;
 use aoc_runner_derive::{aoc, aoc_generator};
-use std::sync::mpsc::{sync_channel, TryRecvError};
-use std::thread;
-use std::time::Duration;
+use std::collections::VecDeque;
+use std::fs;
+use std::io::IsTerminal;
 
 #[aoc_generator(day13)]
 pub fn parse_day13(input: &str) -> Vec<i64> {
@@ -23,6 +25,17 @@ enum Tile {
 }
 
 impl Tile {
+    fn from_id(id: i64) -> Self {
+        match id {
+            0 => Tile::Empty,
+            1 => Tile::Wall,
+            2 => Tile::Block,
+            3 => Tile::HorizontalPaddle,
+            4 => Tile::Ball,
+            _ => panic!("invalid tile"),
+        }
+    }
+
     fn symbol(&self) -> char {
         match self {
             Tile::Empty => ' ',
@@ -34,114 +47,560 @@ impl Tile {
     }
 }
 
-#[aoc(day13, part1)]
-pub fn solve_day13_part1(input: &[i64]) -> usize {
-    let (_tx_in, rx_in) = sync_channel(20);
-    let (tx_out, rx_out) = sync_channel(20);
-    let program = input.to_vec();
-    thread::spawn(move || intcode::execute_threaded(program, rx_in, tx_out, None));
-
-    let mut screen = vec![vec![Tile::Empty; 100]; 100];
-    for x in &rx_out {
-        let y = rx_out
-            .recv()
-            .expect("draw instructions must always have 3 values");
-        let tile = match rx_out
-            .recv()
-            .expect("draw instructions must always have 3 values")
-        {
-            0 => Tile::Empty,
-            1 => Tile::Wall,
-            2 => Tile::Block,
-            3 => Tile::HorizontalPaddle,
-            4 => Tile::Ball,
-            _ => panic!("invalid tile"),
+// Everything shown around the playing field
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Hud {
+    score: i64,
+    lives: usize,
+    blocks_left: usize,
+    paused: bool,
+}
+
+impl Hud {
+    fn text(&self) -> String {
+        format!(
+            " SCORE: {}  LIVES: {}  BLOCKS: {}{} ",
+            self.score,
+            self.lives,
+            self.blocks_left,
+            if self.paused { "  PAUSED" } else { "" }
+        )
+    }
+}
+
+// Somewhere for the game to be drawn
+trait Screen {
+    fn draw(&mut self, x: i64, y: i64, tile: &Tile);
+
+    fn show_hud(&mut self, hud: &Hud);
+
+    // Called whenever the game has finished drawing and is waiting for input
+    fn refresh(&mut self);
+
+    // Called once the game has asked for input, with what changed over the frame just drawn
+    fn frame_complete(&mut self, _frame: &Frame) {}
+
+    // The key pressed since the last frame, for screens that come with a keyboard
+    fn key(&mut self) -> Option<char> {
+        None
+    }
+}
+
+// The colorful live view in a terminal
+struct Curses {
+    window: pancurses::Window,
+}
+
+impl Curses {
+    // `delay` is how many milliseconds to wait for a key each frame, which sets the pace of the game
+    fn new(delay: i32) -> Self {
+        let window = pancurses::initscr();
+        pancurses::curs_set(0);
+        pancurses::noecho();
+        pancurses::start_color();
+        pancurses::init_pair(0, pancurses::COLOR_WHITE, pancurses::COLOR_BLACK);
+        pancurses::init_pair(1, pancurses::COLOR_RED, pancurses::COLOR_BLACK);
+        pancurses::init_pair(2, pancurses::COLOR_BLUE, pancurses::COLOR_BLACK);
+        pancurses::init_pair(3, pancurses::COLOR_YELLOW, pancurses::COLOR_BLACK);
+        window.keypad(true);
+        window.timeout(delay);
+        Curses { window }
+    }
+}
+
+impl Screen for Curses {
+    fn draw(&mut self, x: i64, y: i64, tile: &Tile) {
+        let color = match tile {
+            Tile::Empty | Tile::Wall => 0,
+            Tile::Block => 2,
+            Tile::HorizontalPaddle => 3,
+            Tile::Ball => 1,
         };
-        screen[y as usize][x as usize] = tile;
+        self.window.attrset(pancurses::COLOR_PAIR(color));
+        self.window.mvaddch(y as i32, x as i32, tile.symbol());
+    }
+
+    fn show_hud(&mut self, hud: &Hud) {
+        self.window.attrset(pancurses::COLOR_PAIR(0));
+        self.window.mv(25, 0);
+        self.window.clrtoeol();
+        self.window.addstr(format!("{:#^1$}", hud.text(), 42));
+    }
+
+    fn refresh(&mut self) {
+        self.window.refresh();
+    }
+
+    fn key(&mut self) -> Option<char> {
+        match self.window.getch()? {
+            pancurses::Input::Character(ch) => Some(ch),
+            pancurses::Input::KeyLeft => Some('h'),
+            pancurses::Input::KeyRight => Some('l'),
+            _ => None,
+        }
+    }
+}
+
+impl Drop for Curses {
+    fn drop(&mut self) {
+        pancurses::endwin();
+    }
+}
+
+// An in-memory screen for running without a terminal
+#[derive(Default)]
+struct Framebuffer {
+    screen: SparseGrid<Tile>,
+    hud: Hud,
+    frames: Vec<Frame>,
+}
+
+impl Framebuffer {
+    fn render(&self) -> String {
+        self.screen.render(' ', |_, tile| tile.symbol()) + self.hud.text().trim() + "\n"
+    }
+}
+
+impl Screen for Framebuffer {
+    fn draw(&mut self, x: i64, y: i64, tile: &Tile) {
+        self.screen.insert((x, y), tile.clone());
+    }
+
+    fn show_hud(&mut self, hud: &Hud) {
+        self.hud = hud.clone();
+    }
+
+    fn refresh(&mut self) {}
+
+    fn frame_complete(&mut self, frame: &Frame) {
+        self.frames.push(frame.clone());
     }
-    screen.iter().fold(0, |acc, row| {
-        acc + row.iter().filter(|tile| **tile == Tile::Block).count()
-    })
+}
+
+// What changed over one frame
+#[derive(Clone, Debug, PartialEq)]
+struct Frame {
+    number: usize,
+    blocks_broken: usize,
+    points: i64,
+}
+
+// Everything the game has drawn so far, kept up to date one draw instruction at a time
+#[derive(Clone, Default)]
+struct GameState {
+    screen: SparseGrid<Tile>,
+    score: i64,
+    ball: (i64, i64),
+    // How far the ball moved in the last frame
+    velocity: (i64, i64),
+    paddle: (i64, i64),
+    blocks_left: usize,
+    // How many frames have been completed
+    frames: usize,
+    // The frame in which the last block was broken, once there are none left
+    cleared_in: Option<usize>,
+    // Changes in the current frame so far
+    blocks_broken: usize,
+    points: i64,
+}
+
+impl GameState {
+    // Apply a sequence of (x, y, tile) draw instructions, where (-1, 0) sets the score instead
+    fn consume(&mut self, output: &[i64]) {
+        for instruction in output.chunks(3) {
+            match *instruction {
+                [x, y, id] => self.draw(x, y, id),
+                _ => panic!("draw instructions must always have 3 values"),
+            }
+        }
+    }
+
+    fn draw(&mut self, x: i64, y: i64, id: i64) {
+        if (x, y) == (-1, 0) {
+            self.points += id - self.score;
+            self.score = id;
+            return;
+        }
+        let tile = Tile::from_id(id);
+        match tile {
+            Tile::HorizontalPaddle => self.paddle = (x, y),
+            // The very first time the ball is drawn it hasn't moved from anywhere
+            Tile::Ball if self.frames == 0 => self.ball = (x, y),
+            Tile::Ball => {
+                self.velocity = (x - self.ball.0, y - self.ball.1);
+                self.ball = (x, y);
+            }
+            _ => (),
+        }
+        let was_block = self.screen.insert((x, y), tile.clone()) == Some(Tile::Block);
+        if was_block && tile != Tile::Block {
+            self.blocks_left -= 1;
+            self.blocks_broken += 1;
+            if self.blocks_left == 0 {
+                self.cleared_in = Some(self.frames);
+            }
+        } else if !was_block && tile == Tile::Block {
+            self.blocks_left += 1;
+        }
+    }
+
+    // The game has asked for input, so everything for this frame has been drawn
+    fn complete_frame(&mut self) -> Frame {
+        let frame = Frame {
+            number: self.frames,
+            blocks_broken: self.blocks_broken,
+            points: self.points,
+        };
+        self.frames += 1;
+        self.blocks_broken = 0;
+        self.points = 0;
+        frame
+    }
+
+    fn blocks_left(&self) -> usize {
+        self.blocks_left
+    }
+
+    // How many frames it took to break every block, if they have all been broken
+    fn ticks_to_clear(&self) -> Option<usize> {
+        self.cleared_in
+    }
+
+    // Whether the ball has just bounced off the paddle
+    fn left_paddle(&self) -> bool {
+        self.ball.1 == self.paddle.1 - 2 && self.velocity.1 < 0
+    }
+}
+
+// Something moving the joystick
+trait Controller {
+    // -1 to tilt the joystick left, 0 to leave it neutral and 1 to tilt it right
+    fn joystick(&mut self, state: &GameState, key: Option<char>) -> i64;
+}
+
+// A human at the keyboard, using h/l or the arrow keys
+struct Keyboard;
+
+impl Controller for Keyboard {
+    fn joystick(&mut self, _state: &GameState, key: Option<char>) -> i64 {
+        match key {
+            Some('h') | Some('a') => -1,
+            Some('l') | Some('d') => 1,
+            _ => 0,
+        }
+    }
+}
+
+// Keep the paddle under the ball
+struct Tracking;
+
+impl Controller for Tracking {
+    fn joystick(&mut self, state: &GameState, _key: Option<char>) -> i64 {
+        (state.ball.0 - state.paddle.0).signum()
+    }
+}
+
+// Head for where the ball will come down, assuming it only bounces off the side walls. The
+// prediction is redone every frame so bounces off blocks are caught as they happen.
+struct Predictive;
+
+impl Predictive {
+    fn landing_x(state: &GameState) -> i64 {
+        let (dx, dy) = state.velocity;
+        if dy <= 0 {
+            // Nothing to catch yet, so stay under the ball
+            return state.ball.0;
+        }
+        let ((min_x, _), (max_x, _)) = state.screen.bounds().unwrap();
+        // The ball moves between the walls on either side
+        let (left, right) = (min_x + 1, max_x - 1);
+        let steps = state.paddle.1 - 1 - state.ball.1;
+        let period = 2 * (right - left);
+        if period == 0 {
+            return left;
+        }
+        let unfolded = (state.ball.0 - left + dx * steps).rem_euclid(period);
+        left + if unfolded > right - left {
+            period - unfolded
+        } else {
+            unfolded
+        }
+    }
+}
+
+impl Controller for Predictive {
+    fn joystick(&mut self, state: &GameState, _key: Option<char>) -> i64 {
+        (Predictive::landing_x(state) - state.paddle.0).signum()
+    }
+}
+
+// Play back the moves from an earlier game, keeping the joystick neutral once they run out
+struct Replay {
+    moves: std::vec::IntoIter<i64>,
+}
+
+impl Controller for Replay {
+    fn joystick(&mut self, _state: &GameState, _key: Option<char>) -> i64 {
+        self.moves.next().unwrap_or(0)
+    }
+}
+
+// Pick a controller with AOC_DAY13_CONTROLLER, which is one of "keyboard", "tracking" (the
+// default), "predictive" or "replay:<file>"
+fn controller_from_env() -> Box<dyn Controller> {
+    match options::get(13, "CONTROLLER").as_deref() {
+        Some("keyboard") => Box::new(Keyboard),
+        Some("tracking") | None => Box::new(Tracking),
+        Some("predictive") => Box::new(Predictive),
+        Some(other) => match other.strip_prefix("replay:") {
+            Some(path) => {
+                let moves = fs::read_to_string(path).expect("ERROR: failed to read replay");
+                Box::new(Replay {
+                    moves: parse_day13(moves.trim()).into_iter(),
+                })
+            }
+            None => panic!("ERROR: unknown AOC_DAY13_CONTROLLER {}", other),
+        },
+    }
+}
+
+// The live view if AOC_DAY13_DISPLAY is "curses", or by default when attached to a terminal. Any
+// other value, or no terminal, means headless.
+fn use_curses() -> bool {
+    match options::get(13, "DISPLAY") {
+        Some(display) => display == "curses",
+        None => std::io::stdout().is_terminal(),
+    }
+}
+
+const MAX_STEPS: usize = 1_000_000;
+
+// The arcade cabinet along with what has been drawn on its screen
+#[derive(Clone)]
+struct Game {
+    machine: Machine,
+    state: GameState,
+}
+
+impl Game {
+    fn new(program: &[i64]) -> Self {
+        Game {
+            machine: Machine::new(program),
+            state: GameState::default(),
+        }
+    }
+
+    // Run until the next frame is drawn
+    fn run(&mut self, joystick: Option<i64>, screen: &mut dyn Screen) -> Status {
+        let mut input = joystick.into_iter().collect::<VecDeque<_>>();
+        let mut output = Vec::new();
+        let status = self.machine.run(&mut input, &mut output, MAX_STEPS);
+        self.state.consume(&output);
+        for instruction in output.chunks(3) {
+            if let [x, y, id] = *instruction {
+                if (x, y) != (-1, 0) {
+                    screen.draw(x, y, &Tile::from_id(id));
+                }
+            }
+        }
+        status
+    }
+
+    // Draw the whole screen again, after going back to a checkpoint
+    fn redraw(&self, screen: &mut dyn Screen) {
+        for ((x, y), tile) in self.state.screen.iter() {
+            screen.draw(x, y, tile);
+        }
+    }
+}
+
+// How many balls may be lost before the game is over. The cabinet itself only has one, losing
+// any of the others goes back to the last time the ball left the paddle.
+const LIVES: usize = 3;
+
+// Play the game to completion, returning the final screen and score along with every joystick
+// move made, which can be fed to a `Replay` to play the same game again. Pressing p pauses.
+fn play(
+    program: &[i64],
+    screen: &mut dyn Screen,
+    controller: &mut dyn Controller,
+    lives: usize,
+) -> (Game, Vec<i64>) {
+    let mut game = Game::new(program);
+    let mut checkpoint = game.clone();
+    let mut hud = Hud {
+        lives,
+        ..Hud::default()
+    };
+    let mut moves = Vec::new();
+    let mut joystick = None;
+    loop {
+        let status = game.run(joystick.take(), screen);
+        hud.score = game.state.score;
+        hud.blocks_left = game.state.blocks_left();
+        screen.show_hud(&hud);
+        screen.refresh();
+        match status {
+            Status::NeedsInput => {
+                screen.frame_complete(&game.state.complete_frame());
+                if game.state.left_paddle() {
+                    checkpoint = game.clone();
+                }
+                let mut key = screen.key();
+                while key == Some('p') || hud.paused {
+                    if key == Some('p') {
+                        hud.paused = !hud.paused;
+                        screen.show_hud(&hud);
+                        screen.refresh();
+                    }
+                    key = screen.key();
+                }
+                let new_move = controller.joystick(&game.state, key);
+                moves.push(new_move);
+                joystick = Some(new_move);
+            }
+            Status::Halted if hud.blocks_left > 0 && hud.lives > 1 => {
+                hud.lives -= 1;
+                game = checkpoint.clone();
+                game.redraw(screen);
+            }
+            Status::Halted => return (game, moves),
+            Status::Timeout => panic!("ERROR: the game stopped responding"),
+        }
+    }
+}
+
+#[aoc(day13, part1)]
+pub fn solve_day13_part1(input: &[i64]) -> usize {
+    let (game, _) = play(input, &mut Framebuffer::default(), &mut Tracking, 1);
+    game.state.blocks_left()
 }
 
 #[aoc(day13, part2)]
 pub fn solve_day13_part2(input: &[i64]) -> i64 {
-    let (tx_in, rx_in) = sync_channel(0);
-    let (tx_out, rx_out) = sync_channel(0);
-    let (tx_req, rx_req) = sync_channel(0);
     let mut program = input.to_vec();
     // Insert two quarters
     program[0] = 2;
-    thread::spawn(move || intcode::execute_threaded(program, rx_in, tx_out, Some(&tx_req)));
-
-    let window = pancurses::initscr();
-    pancurses::curs_set(0);
-    pancurses::noecho();
-    pancurses::start_color();
-    pancurses::init_pair(0, pancurses::COLOR_WHITE, pancurses::COLOR_BLACK);
-    pancurses::init_pair(1, pancurses::COLOR_RED, pancurses::COLOR_BLACK);
-    pancurses::init_pair(2, pancurses::COLOR_BLUE, pancurses::COLOR_BLACK);
-    pancurses::init_pair(3, pancurses::COLOR_YELLOW, pancurses::COLOR_BLACK);
-
-    let mut score = 0;
-    window.mvaddstr(25, 0, format!("{:#^1$}", format!(" SCORE: {} ", score), 42));
-    let mut ballx = 0_i64;
-    let mut paddlex = 24_i64;
-    loop {
-        match rx_req.try_recv() {
-            Ok(()) => {
-                tx_in
-                    .send((ballx - paddlex).signum())
-                    .expect("ERROR: failed to send input");
-                window.refresh();
-                thread::sleep(Duration::from_millis(10));
+    let mut controller = controller_from_env();
+    let (game, moves) = if use_curses() {
+        // Slow things down enough for a human to keep up
+        let delay = if options::get(13, "CONTROLLER").as_deref() == Some("keyboard") {
+            100
+        } else {
+            10
+        };
+        play(
+            &program,
+            &mut Curses::new(delay),
+            controller.as_mut(),
+            LIVES,
+        )
+    } else {
+        let mut framebuffer = Framebuffer::default();
+        let result = play(&program, &mut framebuffer, controller.as_mut(), LIVES);
+        options::print(13, "SCREEN", || {
+            let mut screen = framebuffer.render();
+            screen.push_str(&format!(
+                "Finished after {} frames\n",
+                framebuffer.frames.len()
+            ));
+            if let Some(ticks) = result.0.state.ticks_to_clear() {
+                screen.push_str(&format!("Cleared every block in {} ticks\n", ticks));
             }
-            Err(TryRecvError::Empty) => (),
-            Err(TryRecvError::Disconnected) => break,
+            screen
+        });
+        result
+    };
+    options::write(13, "RECORD", || {
+        let moves = moves.iter().map(i64::to_string).collect::<Vec<_>>();
+        moves.join(",")
+    });
+    game.state.score
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn framebuffer() {
+        let mut framebuffer = Framebuffer::default();
+        framebuffer.draw(0, 0, &Tile::Wall);
+        framebuffer.draw(2, 0, &Tile::Wall);
+        framebuffer.draw(1, 1, &Tile::Ball);
+        framebuffer.draw(1, 2, &Tile::HorizontalPaddle);
+        framebuffer.show_hud(&Hud {
+            score: 12,
+            lives: 3,
+            blocks_left: 4,
+            paused: true,
+        });
+        let frame = Frame {
+            number: 0,
+            blocks_broken: 2,
+            points: 30,
         };
-        if let Ok(x) = rx_out.try_recv() {
-            let y = rx_out
-                .recv()
-                .expect("draw instructions must always have 3 values");
-            let z = rx_out
-                .recv()
-                .expect("draw instructions must always have 3 values");
-
-            match z {
-                0 | 1 | 2 | 3 | 4 => {
-                    let tile = match z {
-                        0 => Tile::Empty,
-                        1 => {
-                            window.attrset(pancurses::COLOR_PAIR(0));
-                            Tile::Wall
-                        }
-                        2 => {
-                            window.attrset(pancurses::COLOR_PAIR(2));
-                            Tile::Block
-                        }
-                        3 => {
-                            window.attrset(pancurses::COLOR_PAIR(3));
-                            paddlex = x;
-                            Tile::HorizontalPaddle
-                        }
-                        4 => {
-                            window.attrset(pancurses::COLOR_PAIR(1));
-                            ballx = x;
-                            Tile::Ball
-                        }
-                        _ => unreachable!(),
-                    };
-                    window.mvaddch(y as i32, x as i32, tile.symbol());
-                }
-                new_score if (x, y) == (-1, 0) => {
-                    score = new_score;
-                    window.attrset(pancurses::COLOR_PAIR(0));
-                    window.mvaddstr(25, 0, format!("{:#^1$}", format!(" SCORE: {} ", score), 42));
-                }
-                _ => panic!("Invalid draw instruction"),
-            };
-        }
+        framebuffer.frame_complete(&frame);
+        assert_eq!(
+            framebuffer.render(),
+            "# #\n O \n = \nSCORE: 12  LIVES: 3  BLOCKS: 4  PAUSED\n"
+        );
+        assert_eq!(framebuffer.frames, [frame]);
+    }
+
+    #[test]
+    fn game_state() {
+        let mut state = GameState::default();
+        // Walls either side of two blocks, with the ball and paddle below
+        state.consume(&[0, 0, 1, 4, 0, 1, 1, 0, 2, 2, 0, 2, 2, 2, 4, 2, 4, 3]);
+        assert_eq!(state.blocks_left(), 2);
+        assert_eq!(
+            state.complete_frame(),
+            Frame {
+                number: 0,
+                blocks_broken: 0,
+                points: 0
+            }
+        );
+        // The ball moves up and right, breaking a block
+        state.consume(&[2, 2, 0, 3, 1, 4, 2, 0, 0, -1, 0, 50]);
+        assert_eq!(state.velocity, (1, -1));
+        assert_eq!(state.blocks_left(), 1);
+        assert_eq!(state.ticks_to_clear(), None);
+        assert_eq!(
+            state.complete_frame(),
+            Frame {
+                number: 1,
+                blocks_broken: 1,
+                points: 50
+            }
+        );
+        state.consume(&[3, 1, 0, 2, 0, 4, 1, 0, 0, -1, 0, 100]);
+        assert_eq!(state.velocity, (-1, -1));
+        assert_eq!(state.blocks_left(), 0);
+        assert_eq!(state.ticks_to_clear(), Some(2));
+        assert_eq!(state.score, 100);
+    }
+
+    #[test]
+    fn predict_landing() {
+        // Walls at x = 0 and x = 6, with the paddle on row 10
+        let mut state = GameState::default();
+        state.consume(&[0, 0, 1, 6, 0, 1, 3, 10, 3]);
+        let mut state_with = |velocity, ball| {
+            state.velocity = velocity;
+            state.ball = ball;
+            Predictive::landing_x(&state)
+        };
+        // Down and to the right, landing before reaching a wall
+        assert_eq!(state_with((1, 1), (3, 8)), 4);
+        assert_eq!(state_with((1, 1), (2, 8)), 3);
+        // Off the right wall
+        assert_eq!(state_with((1, 1), (3, 5)), 3);
+        assert_eq!(state_with((1, 1), (4, 5)), 2);
+        // Off the left wall
+        assert_eq!(state_with((-1, 1), (2, 7)), 2);
+        // Going up
+        assert_eq!(state_with((-1, -1), (2, 5)), 2);
     }
-    pancurses::endwin();
-    score
 }
@@ -1,10 +1,15 @@
 extern crate pancurses;
-extern crate rand;
-use crate::intcode;
+use crate::grid::{SparseGrid, DIRECTIONS_4};
+use crate::intcode::{self, Machine};
+use crate::options;
+use crate::search::{self, FnGraph};
 use aoc_runner_derive::{aoc, aoc_generator};
-use rand::Rng;
+use std::collections::{HashSet, VecDeque};
+use std::fs;
+use std::path::PathBuf;
 use std::sync::mpsc::{sync_channel, TryRecvError};
 use std::thread;
+use std::time::Duration;
 
 #[aoc_generator(day15)]
 pub fn parse_day13(input: &str) -> Vec<i64> {
@@ -99,155 +104,323 @@ pub fn solve_day15_part1_manual(input: &[i64]) -> usize {
 
 #[derive(PartialEq, Clone)]
 enum Tile {
-    Unknown,
     Empty,
     Wall,
     Oxygen,
 }
 
-fn get_next_move(map: &[Vec<Tile>], (x, y): (usize, usize)) -> i64 {
-    if map[y - 1][x] == Tile::Unknown {
-        return 1;
-    }
-    if map[y + 1][x] == Tile::Unknown {
-        return 2;
-    }
-    if map[y][x - 1] == Tile::Unknown {
-        return 3;
-    }
-    if map[y][x + 1] == Tile::Unknown {
-        return 4;
-    }
-    // This is of course horribly inefficient, but still solves the maze in about 5 seconds and is
-    // very simple
-    rand::thread_rng().gen_range(1, 5)
-}
-
-// this is dijkstra's
-fn shortest_path(map: &[Vec<Tile>], target: (usize, usize)) -> usize {
-    // Map of unvisited node to distance
-    let mut distances = std::collections::HashMap::new();
-    for (y, row) in map.iter().enumerate() {
-        for (x, tile) in row.iter().enumerate() {
-            if *tile == Tile::Empty || *tile == Tile::Oxygen {
-                distances.insert((x, y), std::usize::MAX);
-            }
+type Point = (i64, i64);
+
+// Where the droid starts
+const START: Point = (0, 0);
+
+const MAX_STEPS: usize = 1_000_000;
+
+// The repair droid, keeping track of where it actually is
+struct Droid {
+    machine: Machine,
+    position: Point,
+}
+
+impl Droid {
+    fn new(program: &[i64]) -> Self {
+        Droid {
+            machine: Machine::new(program),
+            position: START,
         }
     }
-    distances.insert((25, 25), 0);
-    while !distances.is_empty() {
-        let (&current, &current_dist) = distances.iter().min_by_key(|(_, d)| *d).unwrap();
-        distances.remove(&current);
-        if current == target {
-            return current_dist;
-        }
-        for neighbor in [
-            (current.0 + 1, current.1),
-            (current.0 - 1, current.1),
-            (current.0, current.1 + 1),
-            (current.0, current.1 - 1),
-        ]
-        .iter()
-        {
-            distances.entry(*neighbor).and_modify(|neighbor_dist| {
-                *neighbor_dist = std::cmp::min(*neighbor_dist, current_dist + 1)
-            });
-        }
+
+    // Try to move in one of DIRECTIONS_4, which are in the same order as the movement commands,
+    // returning what the droid found there
+    fn step(&mut self, direction: usize) -> Tile {
+        let mut output = Vec::new();
+        let mut input = VecDeque::from(vec![direction as i64 + 1]);
+        self.machine.run(&mut input, &mut output, MAX_STEPS);
+        let (dx, dy) = DIRECTIONS_4[direction];
+        let target = (self.position.0 + dx, self.position.1 + dy);
+        let tile = match output[..] {
+            [0] => return Tile::Wall,
+            [1] => Tile::Empty,
+            [2] => Tile::Oxygen,
+            _ => panic!("ERROR: unexpected droid status {:?}", output),
+        };
+        self.position = target;
+        tile
     }
-    panic!("Couldn't reach target");
 }
 
-fn explore_map(input: &[i64]) -> (Vec<Vec<Tile>>, (usize, usize)) {
-    let (tx_in, rx_in) = sync_channel(0);
-    let (tx_out, rx_out) = sync_channel(0);
-    let (tx_req, rx_req) = sync_channel(0);
-    let program = input.to_vec();
-    thread::spawn(move || intcode::execute_threaded(program, rx_in, tx_out, Some(&tx_req)));
-
-    let mut map = vec![vec![Tile::Unknown; 50]; 50];
+// The direction that undoes a step in `direction`
+fn opposite(direction: usize) -> usize {
+    // North and south, and west and east, are next to each other in DIRECTIONS_4
+    direction ^ 1
+}
 
-    let (mut x, mut y) = (25, 25);
-    map[y][x] = Tile::Empty;
-    let mut new_move = 0;
-
-    let get_new_coords = |new_move, x, y| match new_move {
-        1 => (x, y - 1),
-        2 => (x, y + 1),
-        3 => (x - 1, y),
-        4 => (x + 1, y),
-        _ => unreachable!(),
-    };
+// Map every reachable tile with a depth first search, backtracking the droid along the way it came
+// whenever it runs out of unexplored neighbors. Returns the map and the oxygen system's location.
+fn explore_map(input: &[i64]) -> (SparseGrid<Tile>, Point) {
+    let mut droid = Droid::new(input);
+    let mut map = SparseGrid::new();
+    map.insert(START, Tile::Empty);
+    let mut oxygen = None;
+    // The direction of each step taken to reach the current position
+    let mut backtrack = Vec::new();
     loop {
-        if rx_req.try_recv().is_ok() {
-            tx_in
-                .send({
-                    new_move = get_next_move(&map, (x, y));
-                    new_move
-                })
-                .expect("ERROR: failed to send input");
-        }
-        match rx_out.try_recv() {
-            Ok(0) => {
-                let (newx, newy) = get_new_coords(new_move, x, y);
-                map[newy][newx] = Tile::Wall;
+        let unexplored = SparseGrid::<Tile>::neighbors4(droid.position)
+            .position(|neighbor| !map.contains(neighbor));
+        if let Some(direction) = unexplored {
+            let (dx, dy) = DIRECTIONS_4[direction];
+            let target = (droid.position.0 + dx, droid.position.1 + dy);
+            let tile = droid.step(direction);
+            if tile == Tile::Oxygen {
+                oxygen = Some(target);
             }
-            Ok(1) => {
-                let (newx, newy) = get_new_coords(new_move, x, y);
-                map[newy][newx] = Tile::Empty;
-                x = newx;
-                y = newy;
+            if tile != Tile::Wall {
+                backtrack.push(direction);
             }
-            Ok(2) => {
-                let (newx, newy) = get_new_coords(new_move, x, y);
-                map[newy][newx] = Tile::Oxygen;
-                return (map, (newx, newy));
+            map.insert(target, tile);
+        } else if let Some(direction) = backtrack.pop() {
+            if droid.step(opposite(direction)) == Tile::Wall {
+                panic!("ERROR: droid failed to backtrack from {:?}", droid.position);
             }
-            _ => (),
+        } else {
+            break;
         }
     }
+    (
+        map,
+        oxygen.expect("ERROR: failed to find the oxygen system"),
+    )
+}
+
+// The open tiles next to `point`
+fn open_neighbors(map: &SparseGrid<Tile>, point: Point) -> Vec<(Point, usize)> {
+    SparseGrid::<Tile>::neighbors4(point)
+        .filter(|&neighbor| matches!(map.get(neighbor), Some(Tile::Empty) | Some(Tile::Oxygen)))
+        .map(|neighbor| (neighbor, 1))
+        .collect()
+}
+
+// The points along the shortest route from the start to `target`, including both ends
+fn shortest_path(map: &SparseGrid<Tile>, target: Point) -> Vec<Point> {
+    let graph = FnGraph::new(|&point: &Point| open_neighbors(map, point));
+    // Every move covers one tile, so the manhattan distance never overestimates
+    let manhattan = |&(x, y): &Point| ((x - target.0).abs() + (y - target.1).abs()) as usize;
+    search::astar(&graph, START, |&point| point == target, manhattan)
+        .path_to(&target)
+        .unwrap_or_else(|| panic!("ERROR: can't reach {},{}", target.0, target.1))
+}
+
+// Draw the explored map with the route marked on it
+fn render_route(map: &SparseGrid<Tile>, route: &[Point]) -> String {
+    let route = route.iter().collect::<HashSet<_>>();
+    map.render(' ', |point, tile| match tile {
+        _ if point == START => 'S',
+        Tile::Oxygen => 'O',
+        _ if route.contains(&point) => '*',
+        Tile::Empty => '.',
+        Tile::Wall => '#',
+    })
 }
 
 #[aoc(day15, part1)]
 pub fn solve_day15_part1(input: &[i64]) -> usize {
     let (map, oxy) = explore_map(input);
-    shortest_path(&map, oxy)
+    let route = shortest_path(&map, oxy);
+    options::print(15, "ROUTE", || render_route(&map, &route));
+    route.len() - 1
 }
 
-// this is dijkstra's
-fn longest_path(map: &[Vec<Tile>], start: (usize, usize)) -> usize {
-    // Map of unvisited node to distance
-    let mut distances = std::collections::HashMap::new();
-    for (y, row) in map.iter().enumerate() {
-        for (x, tile) in row.iter().enumerate() {
-            if *tile == Tile::Empty || *tile == Tile::Oxygen {
-                distances.insert((x, y), std::usize::MAX);
+// Spread the oxygen one minute at a time. Each entry holds the tiles newly filled that minute,
+// starting with just the oxygen system at minute 0.
+fn flood(map: &SparseGrid<Tile>, oxygen: Point) -> Vec<Vec<Point>> {
+    let mut filled = HashSet::new();
+    filled.insert(oxygen);
+    let mut minutes = vec![vec![oxygen]];
+    loop {
+        let mut newly_filled = Vec::new();
+        for &point in minutes.last().unwrap().iter() {
+            for (neighbor, _) in open_neighbors(map, point) {
+                if filled.insert(neighbor) {
+                    newly_filled.push(neighbor);
+                }
             }
         }
+        if newly_filled.is_empty() {
+            return minutes;
+        }
+        minutes.push(newly_filled);
     }
-    distances.insert(start, 0);
-    let mut max_dist = 0;
-    while !distances.is_empty() {
-        let (&current, &current_dist) = distances.iter().min_by_key(|(_, d)| *d).unwrap();
-        max_dist = std::cmp::max(max_dist, current_dist);
-        distances.remove(&current);
-        for neighbor in [
-            (current.0 + 1, current.1),
-            (current.0 - 1, current.1),
-            (current.0, current.1 + 1),
-            (current.0, current.1 - 1),
-        ]
-        .iter()
-        {
-            distances.entry(*neighbor).and_modify(|neighbor_dist| {
-                *neighbor_dist = std::cmp::min(*neighbor_dist, current_dist + 1)
-            });
+}
+
+// The map after `minute` minutes of flooding, with the tiles filled that minute highlighted
+fn render_flood(map: &SparseGrid<Tile>, minutes: &[Vec<Point>], minute: usize) -> String {
+    let filled = minutes[..=minute].iter().flatten().collect::<HashSet<_>>();
+    let newly_filled = minutes[minute].iter().collect::<HashSet<_>>();
+    map.render(' ', |point, tile| match tile {
+        _ if newly_filled.contains(&point) => '@',
+        _ if filled.contains(&point) => 'O',
+        Tile::Empty | Tile::Oxygen => '.',
+        Tile::Wall => '#',
+    })
+}
+
+// How many pixels wide and tall each tile is in the PPM frames
+const PIXEL_SIZE: usize = 4;
+
+// A frame as a plain (ASCII) PPM image, using the characters from `render_flood`
+fn flood_ppm(frame: &str) -> String {
+    let lines = frame.lines().collect::<Vec<_>>();
+    let width = lines.iter().map(|line| line.len()).max().unwrap_or(0);
+    let mut out = format!(
+        "P3\n{} {}\n255\n",
+        width * PIXEL_SIZE,
+        lines.len() * PIXEL_SIZE
+    );
+    for line in lines.iter() {
+        let row = (0..width)
+            .flat_map(|x| {
+                let color = match line.as_bytes().get(x) {
+                    Some(b'@') => "128 192 255",
+                    Some(b'O') => "32 64 192",
+                    Some(b'.') => "255 255 255",
+                    Some(b'#') => "64 64 64",
+                    _ => "0 0 0",
+                };
+                std::iter::repeat_n(color, PIXEL_SIZE)
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        for _ in 0..PIXEL_SIZE {
+            out += &row;
+            out.push('\n');
+        }
+    }
+    out
+}
+
+// A line for every minute with how many tiles were filled that minute and how many so far
+fn flood_stats(minutes: &[Vec<Point>]) -> String {
+    let mut total = 0;
+    let mut out = String::new();
+    for (minute, newly_filled) in minutes.iter().enumerate() {
+        total += newly_filled.len();
+        out.push_str(&format!(
+            "Minute {}: {} newly filled, {} filled in total\n",
+            minute,
+            newly_filled.len(),
+            total
+        ));
+    }
+    out
+}
+
+// Where to show the flood, if anywhere
+enum FloodOutput {
+    Terminal,
+    TextFrames(PathBuf),
+    PpmFrames(PathBuf),
+}
+
+impl FloodOutput {
+    // Read from AOC_DAY15_FLOOD, which is one of "curses", "text:<directory>" or "ppm:<directory>"
+    fn from_env() -> Option<Self> {
+        let value = options::get(15, "FLOOD")?;
+        if value == "curses" {
+            Some(FloodOutput::Terminal)
+        } else if let Some(dir) = value.strip_prefix("text:") {
+            Some(FloodOutput::TextFrames(PathBuf::from(dir)))
+        } else if let Some(dir) = value.strip_prefix("ppm:") {
+            Some(FloodOutput::PpmFrames(PathBuf::from(dir)))
+        } else {
+            panic!("ERROR: unknown AOC_DAY15_FLOOD output {}", value);
+        }
+    }
+
+    fn show(&self, map: &SparseGrid<Tile>, minutes: &[Vec<Point>]) {
+        let frames = (0..minutes.len()).map(|minute| render_flood(map, minutes, minute));
+        match self {
+            FloodOutput::Terminal => {
+                let window = pancurses::initscr();
+                pancurses::curs_set(0);
+                for (minute, frame) in frames.enumerate() {
+                    window.mvaddstr(0, 0, format!("Minute {}", minute));
+                    for (y, line) in frame.lines().enumerate() {
+                        window.mvaddstr(y as i32 + 1, 0, line);
+                    }
+                    window.refresh();
+                    thread::sleep(Duration::from_millis(30));
+                }
+                pancurses::endwin();
+            }
+            FloodOutput::TextFrames(dir) | FloodOutput::PpmFrames(dir) => {
+                fs::create_dir_all(dir).expect("ERROR: failed to create frame directory");
+                for (minute, frame) in frames.enumerate() {
+                    let (name, contents) = match self {
+                        FloodOutput::PpmFrames(_) => {
+                            (format!("minute_{:03}.ppm", minute), flood_ppm(&frame))
+                        }
+                        _ => (format!("minute_{:03}.txt", minute), frame),
+                    };
+                    fs::write(dir.join(name), contents).expect("ERROR: failed to write frame");
+                }
+            }
         }
     }
-    max_dist
 }
 
 #[aoc(day15, part2)]
 pub fn solve_day15_part2(input: &[i64]) -> usize {
     let (map, oxy) = explore_map(input);
-    longest_path(&map, oxy)
+    let minutes = flood(&map, oxy);
+    options::print(15, "STATS", || flood_stats(&minutes));
+    if let Some(output) = FloodOutput::from_env() {
+        output.show(&map, &minutes);
+    }
+    minutes.len() - 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_map(input: &str) -> (SparseGrid<Tile>, Point) {
+        let mut map = SparseGrid::new();
+        let mut oxygen = START;
+        for (y, line) in input.lines().enumerate() {
+            for (x, ch) in line.chars().enumerate() {
+                let point = (x as i64, y as i64);
+                match ch {
+                    '#' => map.insert(point, Tile::Wall),
+                    '.' => map.insert(point, Tile::Empty),
+                    'O' => {
+                        oxygen = point;
+                        map.insert(point, Tile::Oxygen)
+                    }
+                    _ => None,
+                };
+            }
+        }
+        (map, oxygen)
+    }
+
+    #[test]
+    fn day15_flood() {
+        const INPUT: &str = " ##   \n\
+                             #..## \n\
+                             #.#..#\n\
+                             #.O.# \n\
+                             \x20###  ";
+        let (map, oxygen) = parse_map(INPUT);
+        let minutes = flood(&map, oxygen);
+        let counts = minutes.iter().map(Vec::len).collect::<Vec<_>>();
+        assert_eq!(counts, [1, 2, 2, 2, 1]);
+        assert!(flood_stats(&minutes).ends_with("Minute 4: 1 newly filled, 8 filled in total\n"));
+        assert_eq!(
+            render_flood(&map, &minutes, 2),
+            " ##   \n#..## \n#@#@.#\n#OOO# \n ###  \n"
+        );
+        let ppm = flood_ppm(&render_flood(&map, &minutes, 4));
+        assert!(ppm.starts_with("P3\n24 20\n255\n"));
+        assert_eq!(ppm.lines().count(), 3 + 20);
+    }
 }
@@ -1,3 +1,4 @@
+use crate::grid::Grid;
 use crate::intcode;
 use aoc_runner_derive::{aoc, aoc_generator};
 use std::sync::mpsc::sync_channel;
@@ -21,75 +22,225 @@ enum Tile {
     BotWest,
 }
 
-#[aoc(day17, part1)]
-pub fn solve_day17_part1(input: &[i64]) -> usize {
+fn parse_view(camera: &str) -> Grid<Tile> {
+    Grid::parse(camera.trim_end(), |ch| match ch {
+        '#' => Tile::Scaffold,
+        '.' => Tile::Empty,
+        '^' => Tile::BotNorth,
+        'v' => Tile::BotSouth,
+        '>' => Tile::BotEast,
+        '<' => Tile::BotWest,
+        _ => panic!("ERROR: {} is not a known char", ch),
+    })
+}
+
+fn read_view(input: &[i64]) -> Grid<Tile> {
     let (_, rx_in) = sync_channel(0);
     let (tx_out, rx_out) = sync_channel(0);
     let program = input.to_vec();
     thread::spawn(move || intcode::execute_threaded(program, rx_in, tx_out, None));
+    parse_view(&rx_out.iter().map(|ch| ch as u8 as char).collect::<String>())
+}
+
+#[aoc(day17, part1)]
+pub fn solve_day17_part1(input: &[i64]) -> usize {
+    let view = read_view(input);
+
+    // Points on the edge of the view have fewer than 4 neighbors so can't be intersections
+    let intersections = view
+        .points()
+        .filter(|&point| {
+            view.neighbors4(point).count() == 4
+                && view
+                    .neighbors4(point)
+                    .all(|neighbor| view[neighbor] != Tile::Empty)
+        })
+        .collect::<std::collections::HashSet<_>>();
+
+    print!(
+        "{}",
+        view.render(|point, tile| match tile {
+            _ if intersections.contains(&point) => 'O',
+            Tile::Scaffold => '#',
+            Tile::Empty => '.',
+            Tile::BotNorth => '^',
+            Tile::BotSouth => 'v',
+            Tile::BotEast => '>',
+            Tile::BotWest => '<',
+        })
+    );
+    intersections.iter().map(|(x, y)| x * y).sum()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Turn {
+    Left,
+    Right,
+}
+
+// A turn followed by moving forward some number of tiles, such as R,10. Only the first step can
+// go straight on without turning, when the robot starts off facing along the scaffold, or turn
+// without moving, when it starts off facing away from it and has to turn around.
+type Step = (Option<Turn>, usize);
 
-    let mut view = Vec::new();
-    view.push(Vec::new());
-    for ch in rx_out {
-        match ch as u8 {
-            b'#' => view.last_mut().unwrap().push(Tile::Scaffold),
-            b'.' => view.last_mut().unwrap().push(Tile::Empty),
-            b'^' => view.last_mut().unwrap().push(Tile::BotNorth),
-            b'v' => view.last_mut().unwrap().push(Tile::BotSouth),
-            b'>' => view.last_mut().unwrap().push(Tile::BotEast),
-            b'<' => view.last_mut().unwrap().push(Tile::BotWest),
-            b'\n' => view.push(Vec::new()),
-            _ => panic!("ERROR: {} is not a known char", ch),
+fn format_steps(steps: &[Step]) -> String {
+    steps
+        .iter()
+        .map(|(turn, forward)| match turn {
+            Some(Turn::Left) if *forward == 0 => "L".to_owned(),
+            Some(Turn::Right) if *forward == 0 => "R".to_owned(),
+            Some(Turn::Left) => format!("L,{}", forward),
+            Some(Turn::Right) => format!("R,{}", forward),
+            None => forward.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+// Follow the scaffold from the robot to its end, turning only when it can't go straight on
+fn scaffold_path(view: &Grid<Tile>) -> Vec<Step> {
+    let scaffold = |point, dir| {
+        view.offset(point, dir)
+            .filter(|&next| view[next] != Tile::Empty)
+    };
+    let mut point = view
+        .position(|tile| *tile != Tile::Scaffold && *tile != Tile::Empty)
+        .expect("ERROR: no vacuum robot in view");
+    let mut dir = match view[point] {
+        Tile::BotNorth => (0, -1),
+        Tile::BotSouth => (0, 1),
+        Tile::BotEast => (1, 0),
+        Tile::BotWest => (-1, 0),
+        _ => unreachable!(),
+    };
+
+    let mut path = Vec::new();
+    let (dx, dy) = dir;
+    if [dir, (dy, -dx), (-dy, dx)]
+        .iter()
+        .all(|&dir| scaffold(point, dir).is_none())
+        && scaffold(point, (-dx, -dy)).is_some()
+    {
+        // Facing away from the scaffold, so turn around in two right turns
+        path.push((Some(Turn::Right), 0));
+        dir = (-dy, dx);
+    }
+    let mut forward = 0;
+    while let Some(next) = scaffold(point, dir) {
+        point = next;
+        forward += 1;
+    }
+    if forward > 0 {
+        path.push((None, forward));
+    }
+    loop {
+        let (dx, dy) = dir;
+        let turn = if scaffold(point, (dy, -dx)).is_some() {
+            dir = (dy, -dx);
+            Turn::Left
+        } else if scaffold(point, (-dy, dx)).is_some() {
+            dir = (-dy, dx);
+            Turn::Right
+        } else {
+            return path;
+        };
+        let mut forward = 0;
+        while let Some(next) = scaffold(point, dir) {
+            point = next;
+            forward += 1;
         }
+        path.push((Some(turn), forward));
     }
-    // get rid of the extra newlines
-    view.pop();
-    view.pop();
-
-    let mut intersections = std::collections::HashSet::new();
-    for y in 1..(view.len() - 1) {
-        for x in 1..(view.first().unwrap().len() - 1) {
-            if view[y - 1][x] != Tile::Empty
-                && view[y + 1][x] != Tile::Empty
-                && view[y][x - 1] != Tile::Empty
-                && view[y][x + 1] != Tile::Empty
-            {
-                intersections.insert((x, y));
-            }
+}
+
+// The longest line the robot's memory accepts for the main routine or a function
+const MAX_LINE: usize = 20;
+
+// A way of splitting the path into calls of three movement functions
+struct Compression {
+    main: Vec<usize>,
+    functions: Vec<Vec<Step>>,
+}
+
+impl Compression {
+    fn routines(&self) -> String {
+        let main = self
+            .main
+            .iter()
+            .map(|&function| (b'A' + function as u8) as char)
+            .map(String::from)
+            .collect::<Vec<_>>()
+            .join(",");
+        let mut routines = main + "\n";
+        for function in 0..3 {
+            let steps = self.functions.get(function).map_or(&[][..], Vec::as_slice);
+            routines += &format_steps(steps);
+            routines.push('\n');
         }
+        routines
     }
+}
 
-    for (y, row) in view.iter().enumerate() {
-        for (x, tile) in row.iter().enumerate() {
-            print!(
-                "{}",
-                match tile {
-                    _ if intersections.contains(&(x, y)) => 'O',
-                    Tile::Scaffold => '#',
-                    Tile::Empty => '.',
-                    Tile::BotNorth => '^',
-                    Tile::BotSouth => 'v',
-                    Tile::BotEast => '>',
-                    Tile::BotWest => '<',
+// Every way of covering the path with a main routine and up to three functions that all fit in
+// MAX_LINE characters. Functions are named in the order they are first called, and no two are the
+// same so that each compression is only found once.
+fn compress(path: &[Step]) -> Vec<Compression> {
+    fn search(
+        path: &[Step],
+        position: usize,
+        main: &mut Vec<usize>,
+        functions: &mut Vec<Vec<Step>>,
+        found: &mut Vec<Compression>,
+    ) {
+        if position == path.len() {
+            found.push(Compression {
+                main: main.clone(),
+                functions: functions.clone(),
+            });
+            return;
+        }
+        // Each call takes a letter and, after the first, a comma
+        if main.len() * 2 + 1 > MAX_LINE {
+            return;
+        }
+        for function in 0..functions.len() {
+            if path[position..].starts_with(&functions[function]) {
+                main.push(function);
+                let next = position + functions[function].len();
+                search(path, next, main, functions, found);
+                main.pop();
+            }
+        }
+        if functions.len() < 3 {
+            main.push(functions.len());
+            for end in position + 1..=path.len() {
+                if format_steps(&path[position..end]).len() > MAX_LINE {
+                    break;
+                }
+                let function = &path[position..end];
+                if functions.iter().any(|other| other == function) {
+                    continue;
                 }
-            );
+                functions.push(function.to_vec());
+                search(path, end, main, functions, found);
+                functions.pop();
+            }
+            main.pop();
         }
-        println!();
     }
-    intersections.iter().map(|(x, y)| x * y).sum()
+    let mut found = Vec::new();
+    search(path, 0, &mut Vec::new(), &mut Vec::new(), &mut found);
+    found
 }
 
-// Entire path:
-// R,10,L,12,R,6,R,10,L,12,R,6,R,6,R,10,R,12,R,6,R,10,L,12,L,12,R,6,R,10,R,12,R,6,
-// R,10,L,12,L,12,R,6,R,10,R,12,R,6,R,10,L,12,L,12,R,6,R,10,R,12,R,6,R,10,L,12,R,6
-
-const MAIN: &str = "C,C,A,B,A,B,A,B,A,C\n";
-const A: &str = "R,6,R,10,R,12,R,6\n";
-const B: &str = "R,10,L,12,L,12\n";
-const C: &str = "R,10,L,12,R,6\n";
-
 #[aoc(day17, part2)]
 fn solve_day17_part2(input: &[i64]) -> i64 {
+    let path = scaffold_path(&read_view(input));
+    let compression = compress(&path)
+        .into_iter()
+        .next()
+        .expect("ERROR: failed to compress the scaffold path");
+
     let (tx_in, rx_in) = sync_channel(0);
     // All we care about is the very last output, so don't block
     let (tx_out, rx_out) = sync_channel(5000);
@@ -99,17 +250,85 @@ fn solve_day17_part2(input: &[i64]) -> i64 {
     program[0] = 2;
     thread::spawn(move || intcode::execute_threaded(program, rx_in, tx_out, Some(&tx_req)));
 
-    for byte in MAIN
-        .as_bytes()
-        .iter()
-        .chain(A.as_bytes().iter())
-        .chain(B.as_bytes().iter())
-        .chain(C.as_bytes().iter())
-        .chain(b"n\n".iter())
-    {
+    for byte in compression.routines().bytes().chain(b"n\n".iter().copied()) {
         rx_req.recv().unwrap();
-        tx_in.send(*byte as i64).unwrap();
+        tx_in.send(byte as i64).unwrap();
     }
 
     rx_out.iter().last().unwrap()
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const EXAMPLE: &str = "#######...#####\n\
+                           #.....#...#...#\n\
+                           #.....#...#...#\n\
+                           ......#...#...#\n\
+                           ......#...###.#\n\
+                           ......#.....#.#\n\
+                           ^########...#.#\n\
+                           ......#.#...#.#\n\
+                           ......#########\n\
+                           ........#...#..\n\
+                           ....#########..\n\
+                           ....#...#......\n\
+                           ....#...#......\n\
+                           ....#...#......\n\
+                           ....#####......";
+
+    #[test]
+    fn day17_path() {
+        let path = scaffold_path(&parse_view(EXAMPLE));
+        assert_eq!(
+            format_steps(&path),
+            "R,8,R,8,R,4,R,4,R,8,L,6,L,2,R,4,R,4,R,8,R,8,R,8,L,6,L,2"
+        );
+    }
+
+    #[test]
+    fn day17_path_turns_around() {
+        let path = scaffold_path(&parse_view("#....\n####>"));
+        assert_eq!(format_steps(&path), "R,R,4,R,1");
+        let path = scaffold_path(&parse_view("v\n#\n#"));
+        assert_eq!(format_steps(&path), "2");
+    }
+
+    #[test]
+    fn day17_compress() {
+        let path = scaffold_path(&parse_view(EXAMPLE));
+        let compressions = compress(&path);
+        assert!(compressions
+            .iter()
+            .any(|c| c.routines() == "A,B,C,B,A,C\nR,8,R,8\nR,4,R,4,R,8\nL,6,L,2\n"));
+        for compression in compressions {
+            let expanded = compression
+                .main
+                .iter()
+                .flat_map(|&function| compression.functions[function].iter().copied())
+                .collect::<Vec<_>>();
+            assert_eq!(expanded, path);
+        }
+    }
+
+    #[test]
+    fn day17_path_facing_along() {
+        let view = parse_view(&EXAMPLE.replace('^', ">"));
+        assert_eq!(
+            format_steps(&scaffold_path(&view)),
+            "8,R,8,R,4,R,4,R,8,L,6,L,2,R,4,R,4,R,8,R,8,R,8,L,6,L,2"
+        );
+    }
+
+    #[test]
+    fn day17_compressions_differ() {
+        // A and B both being R,8 would cover this as A,B as well as A,A
+        let path = [(Some(Turn::Right), 8), (Some(Turn::Right), 8)];
+        let routines = compress(&path)
+            .iter()
+            .map(Compression::routines)
+            .collect::<Vec<_>>();
+        assert_eq!(routines, ["A,A\nR,8\n\n\n", "A\nR,8,R,8\n\n\n"]);
+    }
+}
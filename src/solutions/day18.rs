@@ -1,193 +1,181 @@
+use crate::grid::Grid;
+use crate::options;
+use crate::search::{self, FnGraph};
 use aoc_runner_derive::{aoc, aoc_generator};
-use std::cmp::Ordering;
-use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+use std::collections::{HashMap, HashSet};
 
-type Point = (i32, i32);
+type Point = (usize, usize);
 
 #[aoc_generator(day18)]
-pub fn parse_day18(
-    input: &str,
-) -> (
-    Point,
-    HashSet<Point>,
-    HashMap<Point, u32>,
-    HashMap<Point, u32>,
-) {
-    let mut start = None;
+pub fn parse_day18(input: &str) -> (Point, Grid<bool>, HashMap<Point, u32>, HashMap<Point, u32>) {
+    let map = Grid::parse(input, |ch| ch);
     let mut keys = HashMap::new();
     let mut doors = HashMap::new();
-    let mut walls = HashSet::new();
-    for (y, row) in input.lines().enumerate() {
-        for (x, ch) in row.chars().enumerate() {
-            let x = x as i32;
-            let y = y as i32;
-            match ch {
-                '#' => {
-                    walls.insert((x, y));
-                }
-                '@' => start = Some((x, y)),
-                '.' => (),
-                ch if ch.is_ascii_lowercase() => {
-                    keys.insert((x, y), 1 << (ch as u8 - b'a'));
-                }
-                ch if ch.is_ascii_uppercase() => {
-                    doors.insert((x, y), 1 << (ch.to_ascii_lowercase() as u8 - b'a'));
-                }
-                _ => panic!(),
+    for (point, ch) in map.iter() {
+        match ch {
+            '#' | '@' | '.' => (),
+            ch if ch.is_ascii_lowercase() => {
+                keys.insert(point, 1 << (*ch as u8 - b'a'));
             }
+            ch if ch.is_ascii_uppercase() => {
+                doors.insert(point, 1 << (ch.to_ascii_lowercase() as u8 - b'a'));
+            }
+            _ => panic!(),
         }
     }
-    (start.expect("Failed to find start"), walls, keys, doors)
+    let start = map
+        .position(|ch| *ch == '@')
+        .expect("ERROR: failed to find the start");
+    (start, map.map(|ch| *ch == '#'), keys, doors)
 }
 
-#[derive(Eq)]
-struct State {
-    position: Point,
-    distance: usize,
-    keys: u32,
+// A way of collecting every key
+pub struct Route {
+    pub distance: usize,
+    // The keys in the order they are picked up
+    pub keys: String,
+    // Every point visited along the way, starting with the entrance
+    pub path: Vec<Point>,
 }
 
-// Note that this is "backwards" turning the BinaryHeap into a min queue instead of a max queue
-impl Ord for State {
-    fn cmp(&self, other: &Self) -> Ordering {
-        other.distance.cmp(&self.distance)
-    }
-}
-
-impl PartialOrd for State {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
-    }
+// The shortest walk from one point to a key
+struct Leg {
+    key_pos: Point,
+    key: u32,
+    // The doors that must be opened along the way
+    doors_needed: u32,
+    path: Vec<Point>,
 }
 
-impl PartialEq for State {
-    fn eq(&self, other: &Self) -> bool {
-        self.distance == other.distance
-    }
-}
-
-// This is dijkstra's
 fn find_all_keys(
     start: Point,
-    walls: &HashSet<Point>,
+    walls: &Grid<bool>,
     keys: &HashMap<Point, u32>,
     doors: &HashMap<Point, u32>,
-) -> Option<usize> {
-    let key_dist_cache: HashMap<Point, Vec<(Point, usize, u32, u32)>> = keys
+) -> Option<Route> {
+    let key_dist_cache: HashMap<Point, Vec<Leg>> = keys
         .keys()
         .chain(std::iter::once(&start))
-        .map(|point| (*point, get_distances(*point, &walls, &keys, &doors)))
+        .map(|point| (*point, get_distances(*point, walls, keys, doors)))
         .collect();
 
-    let mut discovered = HashSet::new();
-    let mut pqueue = BinaryHeap::new();
-
-    // 0 distance and 0 keys
-    pqueue.push(State {
-        position: start,
-        distance: 0,
-        keys: 0,
+    // Each node is a position along with the keys collected so far
+    let graph = FnGraph::new(|&(position, held): &(Point, u32)| {
+        key_dist_cache[&position]
+            .iter()
+            .filter(|leg| {
+                // if we don't have the target key yet but do have the keys to open all the doors
+                held & leg.key == 0 && !held & leg.doors_needed == 0
+            })
+            .map(|leg| ((leg.key_pos, held | leg.key), leg.path.len() - 1))
+            .collect()
     });
 
     let all_keys = keys.values().fold(0, |acc, key| acc | key);
+    let result = search::dijkstra(&graph, (start, 0), |&(_, held)| held == all_keys);
+    let goal = result.goal()?;
+    let nodes = result.path_to(goal)?;
 
-    while let Some(current) = pqueue.pop() {
-        if current.keys == all_keys {
-            return Some(current.distance);
-        }
-        if discovered.insert((current.position, current.keys)) {
-            for (new_pos, cost, new_key, _) in key_dist_cache
-                .get(&current.position)
-                .unwrap()
-                .iter()
-                .filter(|(_, _, target_key, doors_needed)| {
-                    // if we don't have the target key yet but do have the keys to open all the doors
-                    current.keys & target_key == 0 && !current.keys & doors_needed == 0
-                })
-            {
-                pqueue.push(State {
-                    position: *new_pos,
-                    distance: current.distance + cost,
-                    keys: current.keys | new_key,
-                });
-            }
-        }
+    let mut route = Route {
+        distance: result.goal_distance()?,
+        keys: String::new(),
+        path: vec![start],
+    };
+    for pair in nodes.windows(2) {
+        let ((from, _), (to, _)) = (pair[0], pair[1]);
+        let leg = key_dist_cache[&from]
+            .iter()
+            .find(|leg| leg.key_pos == to)
+            .unwrap();
+        route.keys.push(key_name(leg.key));
+        route.path.extend(leg.path.iter().skip(1));
     }
-    None
+    Some(route)
+}
+
+fn key_name(key: u32) -> char {
+    (b'a' + key.trailing_zeros() as u8) as char
 }
 
-// This is a bfs
+// The shortest walk to each reachable key
 fn get_distances(
     start: Point,
-    walls: &HashSet<Point>,
+    walls: &Grid<bool>,
     keys: &HashMap<Point, u32>,
     doors: &HashMap<Point, u32>,
-) -> Vec<(Point, usize, u32, u32)> {
-    let mut distances = Vec::new();
-    let mut discovered = HashSet::new();
-    let mut queue = VecDeque::new();
-
-    // 0 distance and no doors seen
-    queue.push_back((start, 0, 0));
+) -> Vec<Leg> {
+    let graph = FnGraph::new(|&point: &Point| {
+        walls
+            .neighbors4(point)
+            .filter(|&neighbor| !walls[neighbor])
+            .map(|neighbor| (neighbor, 1))
+            .collect()
+    });
+    let result = search::bfs(&graph, start, |_| false);
+    keys.iter()
+        .filter_map(|(&key_pos, &key)| {
+            let path = result.path_to(&key_pos)?;
+            let doors_needed = path
+                .iter()
+                .fold(0, |acc, point| acc | doors.get(point).unwrap_or(&0));
+            Some(Leg {
+                key_pos,
+                key,
+                doors_needed,
+                path,
+            })
+        })
+        .collect()
+}
 
-    while let Some((cur_pos, cur_dist, cur_doors)) = queue.pop_front() {
-        if discovered.insert(cur_pos) {
-            if let Some(key) = keys.get(&cur_pos) {
-                distances.push((cur_pos, cur_dist, *key, cur_doors));
-            }
-            for neighbor in [
-                (cur_pos.0 + 1, cur_pos.1),
-                (cur_pos.0 - 1, cur_pos.1),
-                (cur_pos.0, cur_pos.1 + 1),
-                (cur_pos.0, cur_pos.1 - 1),
-            ]
-            .iter()
-            {
-                if !walls.contains(neighbor) {
-                    queue.push_back((
-                        *neighbor,
-                        cur_dist + 1,
-                        cur_doors | doors.get(neighbor).unwrap_or(&0),
-                    ));
-                }
-            }
+// Draw the vault with the route marked on it
+fn render_route(
+    (start, walls, keys, doors): &(Point, Grid<bool>, HashMap<Point, u32>, HashMap<Point, u32>),
+    route: &Route,
+) -> String {
+    let path = route.path.iter().collect::<HashSet<_>>();
+    walls.render(|point, &wall| {
+        if point == *start {
+            '@'
+        } else if let Some(&key) = keys.get(&point) {
+            key_name(key)
+        } else if let Some(&door) = doors.get(&point) {
+            key_name(door).to_ascii_uppercase()
+        } else if wall {
+            '#'
+        } else if path.contains(&point) {
+            '*'
+        } else {
+            '.'
         }
-    }
-    distances
+    })
 }
 
 #[aoc(day18, part1)]
 pub fn solve_day18_part1(
-    (start, walls, keys, doors): &(
-        Point,
-        HashSet<Point>,
-        HashMap<Point, u32>,
-        HashMap<Point, u32>,
-    ),
+    vault: &(Point, Grid<bool>, HashMap<Point, u32>, HashMap<Point, u32>),
 ) -> usize {
-    find_all_keys(*start, &walls, &keys, &doors).expect("There is no path that obtains all keys")
+    let (start, walls, keys, doors) = vault;
+    let route = find_all_keys(*start, walls, keys, doors)
+        .expect("ERROR: there is no path that obtains all keys");
+    options::print(18, "ROUTE", || {
+        format!(
+            "{}Keys collected in order: {}\n",
+            render_route(vault, &route),
+            route.keys
+        )
+    });
+    route.distance
 }
 
 #[aoc(day18, part2)]
 pub fn solve_day18_part2(
-    (start, walls, keys, doors): &(
-        Point,
-        HashSet<Point>,
-        HashMap<Point, u32>,
-        HashMap<Point, u32>,
-    ),
+    (start, walls, keys, doors): &(Point, Grid<bool>, HashMap<Point, u32>, HashMap<Point, u32>),
 ) -> usize {
     let mut walls = walls.clone();
     // Modify the map as instructed
-    for new_wall in [
-        (start.0 + 1, start.1),
-        (start.0 - 1, start.1),
-        (start.0, start.1 + 1),
-        (start.0, start.1 - 1),
-    ]
-    .iter()
-    {
-        walls.insert(*new_wall);
+    for new_wall in walls.neighbors4(*start).collect::<Vec<_>>() {
+        walls[new_wall] = true;
     }
     let walls = walls;
 
@@ -197,22 +185,26 @@ pub fn solve_day18_part2(
             let new_keys = keys
                 .iter()
                 .filter(|((key_x, key_y), _)| {
-                    dx * key_x > dx * start.0 && dy * key_y > dy * start.1
+                    dx * *key_x as i64 > dx * start.0 as i64
+                        && dy * *key_y as i64 > dy * start.1 as i64
                 })
                 .map(|(k, v)| (*k, *v))
                 .collect::<HashMap<_, _>>();
             let new_doors = doors
                 .iter()
                 .filter(|((key_x, key_y), _)| {
-                    dx * key_x > dx * start.0 && dy * key_y > dy * start.1
+                    dx * *key_x as i64 > dx * start.0 as i64
+                        && dy * *key_y as i64 > dy * start.1 as i64
                 })
                 .filter(|(_, key_needed)| {
                     new_keys.iter().find(|(_, key)| key == key_needed).is_some()
                 })
                 .map(|(k, v)| (*k, *v))
                 .collect::<HashMap<_, _>>();
-            find_all_keys((start.0 + dx, start.1 + dy), &walls, &new_keys, &new_doors)
-                .expect("There is no path that obtains all keys")
+            let start = walls.offset(*start, (*dx, *dy)).unwrap();
+            find_all_keys(start, &walls, &new_keys, &new_doors)
+                .expect("ERROR: there is no path that obtains all keys")
+                .distance
         })
         .sum()
 }
@@ -254,4 +246,22 @@ mod test {
                              #################";
         assert_eq!(solve_day18_part1(&parse_day18(INPUT)), 136);
     }
+
+    #[test]
+    fn day18_route() {
+        const INPUT: &str = "########################\n\
+                             #...............b.C.D.f#\n\
+                             #.######################\n\
+                             #.....@.a.B.c.d.A.e.F.g#\n\
+                             ########################";
+        let vault = parse_day18(INPUT);
+        let (start, walls, keys, doors) = &vault;
+        let route = find_all_keys(*start, walls, keys, doors).unwrap();
+        assert_eq!(route.keys, "bacdfeg");
+        assert_eq!(route.path.len() - 1, route.distance);
+        assert_eq!(
+            render_route(&vault, &route).lines().nth(1),
+            Some("#***************b*C*D*f#")
+        );
+    }
 }
@@ -1,4 +1,5 @@
 use crate::intcode;
+use crate::intcode::symbolic::{Expr, Path, SymbolicMachine, Termination};
 use aoc_runner_derive::{aoc, aoc_generator};
 use std::collections::HashMap;
 
@@ -24,19 +25,51 @@ pub fn solve_day19_part1(program: &[i64]) -> i64 {
     count
 }
 
-fn check_coord(x: i64, y: i64, program: &[i64], cache: &mut HashMap<(i64, i64), i64>) -> i64 {
+// Run the probe with symbolic coordinates. Away from the emitter every path halts and the
+// conditions along it describe exactly which part of the plane it covers, e.g. the beam edges come
+// out as `127*x*x + 21*x*y - 89*y*y >= 0`.
+fn probe_paths(program: &[i64]) -> Vec<Path> {
+    let mut machine = SymbolicMachine::new(program);
+    machine.push_input(Expr::symbol("x"));
+    machine.push_input(Expr::symbol("y"));
+    machine
+        .explore(100_000, 100)
+        .into_iter()
+        .filter(|path| path.termination == Termination::Halted)
+        .collect()
+}
+
+fn check_coord(
+    x: i64,
+    y: i64,
+    program: &[i64],
+    paths: &[Path],
+    cache: &mut HashMap<(i64, i64), i64>,
+) -> i64 {
     if let Some(value) = cache.get(&(x, y)) {
         return *value;
     }
-    let value = *intcode::execute(program, &[x, y]).last().unwrap();
+    let assignment = [("x", x), ("y", y)].iter().cloned().collect();
+    let value = paths
+        .iter()
+        .find(|path| path.satisfied_by(&assignment))
+        .and_then(|path| path.outputs.last().unwrap().eval(&assignment))
+        // Paths that didn't finish exploring or overflowed, fall back to running the program
+        .unwrap_or_else(|| *intcode::execute(program, &[x, y]).last().unwrap());
     cache.insert((x, y), value);
     value
 }
 
-fn check(x: i64, y: i64, program: &[i64], cache: &mut HashMap<(i64, i64), i64>) -> bool {
+fn check(
+    x: i64,
+    y: i64,
+    program: &[i64],
+    paths: &[Path],
+    cache: &mut HashMap<(i64, i64), i64>,
+) -> bool {
     for y in y..(y + 100) {
         for x in x..(x + 100) {
-            if 0 == check_coord(x, y, program, cache) {
+            if 0 == check_coord(x, y, program, paths, cache) {
                 return false;
             }
         }
@@ -46,10 +79,11 @@ fn check(x: i64, y: i64, program: &[i64], cache: &mut HashMap<(i64, i64), i64>)
 
 #[aoc(day19, part2)]
 fn solve_day19_part2(program: &[i64]) -> i64 {
+    let paths = probe_paths(program);
     let mut cache = HashMap::new();
     for y in 1000..10000 {
         for x in 0..4000 {
-            if check(x, y, program, &mut cache) {
+            if check(x, y, program, &paths, &mut cache) {
                 return 10000 * x + y;
             }
         }
@@ -1,3 +1,4 @@
+use crate::intcode::symbolic::{SymbolicMachine, Termination};
 use aoc_runner_derive::{aoc, aoc_generator};
 
 #[aoc_generator(day2)]
@@ -43,10 +44,46 @@ pub fn solve_day2_part1(input: &[usize]) -> usize {
 
 #[aoc(day2, part2)]
 pub fn solve_day2_part2(input: &[usize]) -> usize {
-    const TARGET: usize = 19_690_720;
+    const TARGET: i64 = 19_690_720;
+    // Run the program once with the noun and verb left as symbols. The output turns out to be
+    // affine in both, so for each noun the verb can be solved for directly.
+    let program = input.iter().map(|x| *x as i64).collect::<Vec<_>>();
+    let mut machine = SymbolicMachine::new(&program);
+    machine.set_symbol(1, "noun");
+    machine.set_symbol(2, "verb");
+    let paths = machine.explore(10_000, 100);
+    let outputs = paths
+        .iter()
+        .map(|path| {
+            if path.termination != Termination::Halted {
+                panic!("ERROR: a path stopped early, {:?}", path.termination);
+            }
+            let output = path
+                .memory(0)
+                .expect("ERROR: output depends on unknown memory");
+            assert!(output.is_affine(), "ERROR: {} is not affine", output);
+            (path, output)
+        })
+        .collect::<Vec<_>>();
+
     (0..100)
-        .flat_map(|noun| (0..100).map(move |verb| (noun, verb)))
-        .find(|(noun, verb)| compute(input, *noun, *verb) == TARGET)
-        .map(|(noun, verb)| 100 * noun + verb)
-        .unwrap()
+        .find_map(|noun| {
+            outputs.iter().find_map(|(path, output)| {
+                let output = output.substitute("noun", noun);
+                let coefficient = output.coefficient(&["verb"]);
+                let remainder = TARGET - output.coefficient(&[]);
+                // The verb doesn't change the output for this noun, so it can't be solved for
+                if coefficient == 0 || remainder % coefficient != 0 {
+                    return None;
+                }
+                let verb = remainder / coefficient;
+                let assignment = [("noun", noun), ("verb", verb)].iter().cloned().collect();
+                if (0..100).contains(&verb) && path.satisfied_by(&assignment) {
+                    Some((100 * noun + verb) as usize)
+                } else {
+                    None
+                }
+            })
+        })
+        .expect("ERROR: no noun and verb give the target")
 }
@@ -1,84 +1,81 @@
+use crate::grid::Grid;
+use crate::options;
+use crate::search::{self, FnGraph};
 use aoc_runner_derive::{aoc, aoc_generator};
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 
 type Point = (usize, usize);
 
+pub struct Maze {
+    tiles: Grid<char>,
+    outer_portals: HashMap<Point, Point>,
+    inner_portals: HashMap<Point, Point>,
+    // The name of the portal at each end of it
+    labels: HashMap<Point, String>,
+    start: Point,
+    end: Point,
+}
+
 #[aoc_generator(day20)]
-pub fn parse_day20(
-    input: &str,
-) -> (
-    HashSet<Point>,
-    HashMap<Point, Point>,
-    HashMap<Point, Point>,
-    Point,
-    Point,
-) {
-    let input = input
-        .lines()
-        .map(|line| line.chars().collect::<Vec<_>>())
-        .collect::<Vec<_>>();
-    let mut map = HashSet::new();
+pub fn parse_day20(input: &str) -> Maze {
+    let input = Grid::parse(input, |ch| ch);
     let mut outer_portals = HashMap::new();
     let mut inner_portals = HashMap::new();
-    for y in 0..input.len() {
-        for x in 0..input[y].len() {
-            match input[y][x] {
-                '.' => {
-                    map.insert((x, y));
-                }
-                '#' | ' ' => (),
-                ch1 => {
-                    let is_ch = |ch| ch != '.' && ch != '#' && ch != ' ';
-                    if let Some((ch2, portal)) = if x + 1 < input[y].len() && is_ch(input[y][x + 1])
+    let mut labels = HashMap::new();
+    for ((x, y), &ch) in input.iter() {
+        match ch {
+            '.' | '#' | ' ' => (),
+            ch1 => {
+                let is_ch = |ch| ch != '.' && ch != '#' && ch != ' ';
+                if let Some((ch2, portal)) = if x + 1 < input.width() && is_ch(input[(x + 1, y)]) {
+                    let portal = if let Some('.') = input.get((x + 2, y)) {
+                        (x + 2, y)
+                    } else if x > 0 && input[(x - 1, y)] == '.' {
+                        (x - 1, y)
+                    } else {
+                        panic!("ERROR: failed to read portal at {},{}", x, y);
+                    };
+                    Some((input[(x + 1, y)], portal))
+                } else if y + 1 < input.height() && is_ch(input[(x, y + 1)]) {
+                    let portal = if let Some('.') = input.get((x, y + 2)) {
+                        (x, y + 2)
+                    } else if y > 1 && input[(x, y - 1)] == '.' {
+                        (x, y - 1)
+                    } else {
+                        panic!("ERROR: failed to read portal at {},{}", x, y);
+                    };
+                    Some((input[(x, y + 1)], portal))
+                } else {
+                    None
+                } {
+                    labels.insert(portal, format!("{}{}", ch1, ch2));
+                    // Outer portal
+                    if portal.0 == 2
+                        || portal.1 == 2
+                        || portal.0 == input.width() - 3
+                        || portal.1 == input.height() - 3
                     {
-                        let portal = if let Some('.') = input[y].get(x + 2) {
-                            (x + 2, y)
-                        } else if let Some('.') = input[y].get(x - 1) {
-                            (x - 1, y)
+                        if let Some((first, _)) = outer_portals.get_mut(&(ch1, ch2)) {
+                            *first = portal;
                         } else {
-                            panic!("ERROR: failed to read portal at {},{}", x, y);
-                        };
-                        Some((input[y][x + 1], portal))
-                    } else if y + 1 < input.len() && is_ch(input[y + 1][x]) {
-                        let portal = if y + 2 < input.len() && input[y + 2][x] == '.' {
-                            (x, y + 2)
-                        } else if y - 1 > 0 && input[y - 1][x] == '.' {
-                            (x, y - 1)
+                            outer_portals.insert((ch1, ch2), (portal, (0, 0)));
+                        }
+                        if let Some((_, second)) = inner_portals.get_mut(&(ch1, ch2)) {
+                            *second = portal;
                         } else {
-                            panic!("ERROR: failed to read portal at {},{}", x, y);
-                        };
-                        Some((input[y + 1][x], portal))
+                            inner_portals.insert((ch1, ch2), ((0, 0), portal));
+                        }
                     } else {
-                        None
-                    } {
-                        // Outer portal
-                        if portal.0 == 2
-                            || portal.1 == 2
-                            || portal.0 == input[portal.1].len() - 3
-                            || portal.1 == input.len() - 3
-                        {
-                            if let Some((first, _)) = outer_portals.get_mut(&(ch1, ch2)) {
-                                *first = portal;
-                            } else {
-                                outer_portals.insert((ch1, ch2), (portal, (0, 0)));
-                            }
-                            if let Some((_, second)) = inner_portals.get_mut(&(ch1, ch2)) {
-                                *second = portal;
-                            } else {
-                                inner_portals.insert((ch1, ch2), ((0, 0), portal));
-                            }
+                        // Inner portal
+                        if let Some((first, _)) = inner_portals.get_mut(&(ch1, ch2)) {
+                            *first = portal;
+                        } else {
+                            inner_portals.insert((ch1, ch2), (portal, (0, 0)));
+                        }
+                        if let Some((_, second)) = outer_portals.get_mut(&(ch1, ch2)) {
+                            *second = portal;
                         } else {
-                            // Inner portal
-                            if let Some((first, _)) = inner_portals.get_mut(&(ch1, ch2)) {
-                                *first = portal;
-                            } else {
-                                inner_portals.insert((ch1, ch2), (portal, (0, 0)));
-                            }
-                            if let Some((_, second)) = outer_portals.get_mut(&(ch1, ch2)) {
-                                *second = portal;
-                            } else {
-                                outer_portals.insert((ch1, ch2), ((0, 0), portal));
-                            }
+                            outer_portals.insert((ch1, ch2), ((0, 0), portal));
                         }
                     }
                 }
@@ -87,123 +84,177 @@ pub fn parse_day20(
     }
     let start = outer_portals
         .remove(&('A', 'A'))
-        .expect("Failed to read AA")
+        .expect("ERROR: failed to read AA")
         .0;
     let end = outer_portals
         .remove(&('Z', 'Z'))
-        .expect("Failed to read ZZ")
+        .expect("ERROR: failed to read ZZ")
         .0;
     inner_portals.remove(&('A', 'A'));
     inner_portals.remove(&('Z', 'Z'));
     let outer_portals = outer_portals.iter().map(|(_, points)| *points).collect();
     let inner_portals = inner_portals.iter().map(|(_, points)| *points).collect();
-    (map, outer_portals, inner_portals, start, end)
+    Maze {
+        tiles: input,
+        outer_portals,
+        inner_portals,
+        labels,
+        start,
+        end,
+    }
 }
 
-// this is dijkstra's
-#[aoc(day20, part1)]
-pub fn solve_day20_part1(
-    (map, outer_portals, inner_portals, start, end): &(
-        HashSet<Point>,
-        HashMap<Point, Point>,
-        HashMap<Point, Point>,
-        Point,
-        Point,
-    ),
-) -> usize {
-    // Map of node to distance
-    let mut distances: HashMap<Point, usize> =
-        map.iter().map(|point| (*point, std::usize::MAX)).collect();
-    distances.insert(*start, 0);
-    while !distances.is_empty() {
-        let (&current, &current_dist) = distances.iter().min_by_key(|(_, d)| *d).unwrap();
-        if current == *end {
-            return current_dist;
-        }
-        distances.remove(&current);
-        let mut neighbors = vec![
-            (current.0 + 1, current.1),
-            (current.0 - 1, current.1),
-            (current.0, current.1 + 1),
-            (current.0, current.1 - 1),
-        ];
-        if let Some(portal_target) = outer_portals.get(&current) {
-            neighbors.push(*portal_target);
-        }
-        if let Some(portal_target) = inner_portals.get(&current) {
-            neighbors.push(*portal_target);
-        }
-        for neighbor in neighbors.iter() {
-            distances.entry(*neighbor).and_modify(|neighbor_dist| {
-                let dist = current_dist + 1;
-                if dist < *neighbor_dist {
-                    *neighbor_dist = dist;
-                }
-            });
-        }
-    }
-    // Indicates failure
-    0
+// A trip through a portal
+#[derive(Debug, PartialEq)]
+pub struct Hop {
+    pub portal: String,
+    pub from_depth: usize,
+    pub to_depth: usize,
 }
 
-// this is dijkstra's
-#[aoc(day20, part2)]
-pub fn solve_day20_part2(
-    (map, outer_portals, inner_portals, start, end): &(
-        HashSet<Point>,
-        HashMap<Point, Point>,
-        HashMap<Point, Point>,
-        Point,
-        Point,
-    ),
-) -> usize {
-    // Map of node to distance
-    let mut distances: HashMap<(usize, usize, usize), usize> = map
-        .iter()
-        .map(|point| ((point.0, point.1, 0), std::usize::MAX))
-        .collect();
-    let mut depth = 0;
-    distances.insert((start.0, start.1, 0), 0);
-    while !distances.is_empty() {
-        let (&current, &current_dist) = distances.iter().min_by_key(|(_, d)| *d).unwrap();
-        if current == (end.0, end.1, 0) {
-            return current_dist;
-        }
-        distances.remove(&current);
-        let mut neighbors = vec![
-            (current.0 + 1, current.1, current.2),
-            (current.0 - 1, current.1, current.2),
-            (current.0, current.1 + 1, current.2),
-            (current.0, current.1 - 1, current.2),
-        ];
+pub struct Route {
+    // Every point visited along the way with the level it is on, the outermost level being 0
+    pub path: Vec<(usize, usize, usize)>,
+    pub hops: Vec<Hop>,
+}
+
+// How many levels deep the search goes before giving up on a route
+const MAX_DEPTH: usize = 100;
+
+// The shortest route from AA to ZZ. If `recursive`, inner portals lead one level down and outer
+// portals one level up, otherwise everything stays on level 0.
+fn find_route(maze: &Maze, recursive: bool) -> Option<Route> {
+    let graph = FnGraph::new(|&(x, y, depth): &(usize, usize, usize)| {
+        let mut neighbors = maze
+            .tiles
+            .neighbors4((x, y))
+            .filter(|&neighbor| maze.tiles[neighbor] == '.')
+            .map(|(x, y)| ((x, y, depth), 1))
+            .collect::<Vec<_>>();
         // Outer portals dont work on the topmost level
-        if current.2 > 0 {
-            if let Some(portal_target) = outer_portals.get(&(current.0, current.1)) {
-                neighbors.push((portal_target.0, portal_target.1, current.2 - 1));
+        if !recursive || depth > 0 {
+            if let Some(portal_target) = maze.outer_portals.get(&(x, y)) {
+                let depth = if recursive { depth - 1 } else { depth };
+                neighbors.push(((portal_target.0, portal_target.1, depth), 1));
             }
         }
-        // Limit to depth 20
-        if depth < 100 {
-            if let Some(portal_target) = inner_portals.get(&(current.0, current.1)) {
-                neighbors.push((portal_target.0, portal_target.1, current.2 + 1));
-                // If needed add a layer to the map
-                if depth < current.2 + 1 {
-                    depth += 1;
-                    for point in map.iter() {
-                        distances.insert((point.0, point.1, depth), std::usize::MAX);
-                    }
-                }
+        if !recursive || depth < MAX_DEPTH {
+            if let Some(portal_target) = maze.inner_portals.get(&(x, y)) {
+                let depth = if recursive { depth + 1 } else { depth };
+                neighbors.push(((portal_target.0, portal_target.1, depth), 1));
             }
         }
-        for neighbor in neighbors.iter() {
-            distances.entry(*neighbor).and_modify(|neighbor_dist| {
-                let dist = current_dist + 1;
-                if dist < *neighbor_dist {
-                    *neighbor_dist = dist;
-                }
-            });
+        neighbors
+    });
+    let (start, end) = ((maze.start.0, maze.start.1, 0), (maze.end.0, maze.end.1, 0));
+    let path = search::bfs(&graph, start, |&node| node == end).path_to(&end)?;
+
+    // Walking never moves more than one tile, so any bigger jump went through a portal
+    let hops = path
+        .windows(2)
+        .filter(|pair| {
+            let ((x1, y1, d1), (x2, y2, d2)) = (pair[0], pair[1]);
+            d1 != d2 || x1.max(x2) - x1.min(x2) + y1.max(y2) - y1.min(y2) > 1
+        })
+        .map(|pair| Hop {
+            portal: maze.labels[&(pair[0].0, pair[0].1)].clone(),
+            from_depth: pair[0].2,
+            to_depth: pair[1].2,
+        })
+        .collect();
+    Some(Route { path, hops })
+}
+
+// Draw the maze with the route marked on it, squashing every level onto one map, followed by the
+// portals taken
+fn render_route(maze: &Maze, route: &Route) -> String {
+    let path = route
+        .path
+        .iter()
+        .map(|&(x, y, _)| (x, y))
+        .collect::<std::collections::HashSet<_>>();
+    let mut out = maze.tiles.render(|point, &ch| {
+        if ch == '.' && path.contains(&point) {
+            '*'
+        } else {
+            ch
         }
+    });
+    for hop in route.hops.iter() {
+        if hop.from_depth == hop.to_depth {
+            out += &format!("{}\n", hop.portal);
+        } else {
+            out += &format!(
+                "{} from level {} to level {}\n",
+                hop.portal, hop.from_depth, hop.to_depth
+            );
+        }
+    }
+    out
+}
+
+#[aoc(day20, part1)]
+pub fn solve_day20_part1(maze: &Maze) -> usize {
+    let route = find_route(maze, false).expect("ERROR: ZZ can't be reached");
+    options::print(20, "ROUTE", || render_route(maze, &route));
+    route.path.len() - 1
+}
+
+#[aoc(day20, part2)]
+pub fn solve_day20_part2(maze: &Maze) -> usize {
+    let route = find_route(maze, true).expect("ERROR: ZZ can't be reached");
+    options::print(20, "ROUTE", || render_route(maze, &route));
+    route.path.len() - 1
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Leading spaces matter here so the usual line continuations can't be used
+    const INPUT: &str = concat!(
+        "         A           \n",
+        "         A           \n",
+        "  #######.#########  \n",
+        "  #######.........#  \n",
+        "  #######.#######.#  \n",
+        "  #######.#######.#  \n",
+        "  #######.#######.#  \n",
+        "  #####  B    ###.#  \n",
+        "BC...##  C    ###.#  \n",
+        "  ##.##       ###.#  \n",
+        "  ##...DE  F  ###.#  \n",
+        "  #####    G  ###.#  \n",
+        "  #########.#####.#  \n",
+        "DE..#######...###.#  \n",
+        "  #.#########.###.#  \n",
+        "FG..#########.....#  \n",
+        "  ###########.#####  \n",
+        "             Z       \n",
+        "             Z       \n",
+    );
+
+    #[test]
+    fn day20_example1() {
+        let maze = parse_day20(INPUT);
+        assert_eq!(solve_day20_part1(&maze), 23);
+        let route = find_route(&maze, false).unwrap();
+        let portals = route
+            .hops
+            .iter()
+            .map(|hop| &hop.portal[..])
+            .collect::<Vec<_>>();
+        assert_eq!(portals, ["BC", "DE", "FG"]);
+        assert_eq!(
+            render_route(&maze, &route).lines().nth(3),
+            Some("  #######*........#  ")
+        );
+    }
+
+    #[test]
+    fn day20_recursive_example1() {
+        let maze = parse_day20(INPUT);
+        assert_eq!(solve_day20_part2(&maze), 26);
+        assert!(find_route(&maze, true).unwrap().hops.is_empty());
     }
-    // Indicates failure
-    0
 }
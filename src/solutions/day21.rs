@@ -1,8 +1,7 @@
 use crate::intcode;
+use crate::intcode::transcript;
+use crate::springscript::{self, Mode, Script};
 use aoc_runner_derive::{aoc, aoc_generator};
-use std::collections::VecDeque;
-use std::sync::mpsc::sync_channel;
-use std::thread;
 
 #[aoc_generator(day21)]
 pub fn parse_day21(input: &str) -> Vec<i64> {
@@ -15,41 +14,30 @@ pub fn parse_day21(input: &str) -> Vec<i64> {
 //#[aoc(day21, part1, interactive)]
 #[allow(dead_code)]
 pub fn solve_day21_part1_interactive(program: &[i64]) -> i64 {
-    let (tx_in, rx_in) = sync_channel(0);
-    let (tx_out, rx_out) = sync_channel(0);
-    let (tx_req, rx_req) = sync_channel(0);
-    let program = program.to_vec();
-    thread::spawn(move || intcode::execute_threaded(program, rx_in, tx_out, Some(&tx_req)));
+    transcript::interactive_session(program, 21, "")
+        .0
+        .expect("ERROR: the springdroid fell into space")
+}
 
-    let mut input_buffer = VecDeque::new();
-    loop {
-        match rx_req.try_recv() {
-            Ok(()) => {
-                tx_in
-                    .send({
-                        if let Some(ch) = input_buffer.pop_front() {
-                            ch as i64
-                        } else {
-                            let mut input = String::new();
-                            std::io::stdin().read_line(&mut input).unwrap();
-                            for ch in input.chars() {
-                                input_buffer.push_back(ch);
-                            }
-                            input_buffer.pop_front().unwrap() as i64
-                        }
-                    })
-                    .expect("ERROR: failed to send input");
-            }
-            _ => (),
-        };
-        match rx_out.try_recv() {
-            Ok(out) if 0 <= out && out < 128 => {
-                print!("{}", std::char::from_u32(out as u32).unwrap());
-            }
-            Ok(out) => return out,
-            _ => (),
-        }
+// Run a script on the springdroid, returning the hull it fell on if it didn't make it across
+fn run_script(program: &[i64], script: &Script) -> Result<i64, String> {
+    let mut machine = intcode::Machine::new(program);
+    let mut input = script.to_string().chars().map(|ch| ch as i64).collect();
+    let mut output = Vec::new();
+    machine.run(&mut input, &mut output, usize::MAX);
+    if let Some(&damage) = output.last().filter(|&&out| out >= 128) {
+        return Ok(damage);
     }
+    // The bottom line of the first frame is the hull with the springdroid on its first tile
+    let output = output
+        .iter()
+        .map(|&out| out as u8 as char)
+        .collect::<String>();
+    Err(output
+        .lines()
+        .find(|line| line.starts_with('#'))
+        .expect("ERROR: no hull in the springdroid's output")
+        .to_owned())
 }
 
 #[aoc(day21, part1, auto)]
@@ -61,40 +49,24 @@ pub fn solve_day21_part1_auto(program: &[i64]) -> i64 {
                                  NOT J J\n\
                                  AND D J\n\
                                  WALK\n";
-    let (tx_in, rx_in) = sync_channel(0);
-    let (tx_out, rx_out) = sync_channel(5000);
-    let (tx_req, rx_req) = sync_channel(0);
-    let program = program.to_vec();
-    thread::spawn(move || intcode::execute_threaded(program, rx_in, tx_out, Some(&tx_req)));
-
-    for ch in SPRING_SCRIPT.chars() {
-        rx_req.recv().unwrap();
-        tx_in.send(ch as i64).unwrap();
-    }
-    rx_out.iter().last().unwrap()
+    let script = Script::parse(SPRING_SCRIPT).unwrap_or_else(|err| panic!("ERROR: {}", err));
+    run_script(program, &script)
+        .unwrap_or_else(|hull| panic!("ERROR: the springdroid fell into space at {}", hull))
 }
 
 #[aoc(day21, part2, auto)]
 pub fn solve_day21_part2_auto(program: &[i64]) -> i64 {
-    const SPRING_SCRIPT: &str = "NOT J J\n\
-                                 AND A J\n\
-                                 AND B J\n\
-                                 AND C J\n\
-                                 NOT J J\n\
-                                 AND D J\n\
-                                 OR H T\n\
-                                 OR E T\n\
-                                 AND T J \n\
-                                 RUN\n";
-    let (tx_in, rx_in) = sync_channel(0);
-    let (tx_out, rx_out) = sync_channel(5000);
-    let (tx_req, rx_req) = sync_channel(0);
-    let program = program.to_vec();
-    thread::spawn(move || intcode::execute_threaded(program, rx_in, tx_out, Some(&tx_req)));
+    let script = springscript::synthesize(Mode::Run, |script| run_script(program, script).err())
+        .unwrap_or_else(|error| panic!("ERROR: failed to synthesize a springscript, {}", error));
+    run_script(program, &script).unwrap()
+}
 
-    for ch in SPRING_SCRIPT.chars() {
-        rx_req.recv().unwrap();
-        tx_in.send(ch as i64).unwrap();
-    }
-    rx_out.iter().last().unwrap()
+#[aoc(day21, part2, compiled)]
+pub fn solve_day21_part2_compiled(program: &[i64]) -> i64 {
+    // Jump if there is a hole in the next three tiles and ground to land on, but only if we can
+    // either step forward or jump again straight after landing
+    let script = springscript::compile("!(A & B & C) & D & (E | H)", Mode::Run)
+        .unwrap_or_else(|err| panic!("ERROR: {}", err));
+    run_script(program, &script)
+        .unwrap_or_else(|hull| panic!("ERROR: the springdroid fell into space at {}", hull))
 }
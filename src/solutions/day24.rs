@@ -1,3 +1,4 @@
+use crate::grid::Grid;
 use aoc_runner_derive::{aoc, aoc_generator};
 use std::collections::{HashMap, HashSet};
 
@@ -8,74 +9,53 @@ pub enum Tile {
 }
 
 #[aoc_generator(day24)]
-pub fn parse_day24(input: &str) -> [[Tile; 5]; 5] {
-    let mut bugs = [[Tile::Empty; 5]; 5];
-    for (y, line) in input.lines().enumerate() {
-        for (x, ch) in line.chars().enumerate() {
-            bugs[y][x] = match ch {
-                '#' => Tile::Bug,
-                '.' => Tile::Empty,
-                _ => panic!("ERROR: {} is not a valid tile"),
-            };
-        }
-    }
-    bugs
+pub fn parse_day24(input: &str) -> Grid<Tile> {
+    Grid::parse(input, |ch| match ch {
+        '#' => Tile::Bug,
+        '.' => Tile::Empty,
+        _ => panic!("ERROR: {} is not a valid tile", ch),
+    })
 }
 
-fn step(bugs: &mut [[Tile; 5]; 5]) {
+fn step(bugs: &mut Grid<Tile>) {
     let mut next_bugs = bugs.clone();
-    for y in 0..5 {
-        for x in 0..5 {
-            let neighbor_count = {
-                let mut count = 0;
-                if x > 0 && bugs[y][x - 1] == Tile::Bug {
-                    count += 1;
-                }
-                if y > 0 && bugs[y - 1][x] == Tile::Bug {
-                    count += 1;
-                }
-                if x < 4 && bugs[y][x + 1] == Tile::Bug {
-                    count += 1;
-                }
-                if y < 4 && bugs[y + 1][x] == Tile::Bug {
-                    count += 1;
-                }
-                count
-            };
-            match (bugs[y][x], neighbor_count) {
-                (Tile::Empty, 1) | (Tile::Empty, 2) => next_bugs[y][x] = Tile::Bug,
-                (Tile::Bug, count) if count != 1 => next_bugs[y][x] = Tile::Empty,
-                _ => (),
-            }
+    for (point, tile) in bugs.iter() {
+        let neighbor_count = bugs
+            .neighbors4(point)
+            .filter(|&neighbor| bugs[neighbor] == Tile::Bug)
+            .count();
+        match (tile, neighbor_count) {
+            (Tile::Empty, 1) | (Tile::Empty, 2) => next_bugs[point] = Tile::Bug,
+            (Tile::Bug, count) if count != 1 => next_bugs[point] = Tile::Empty,
+            _ => (),
         }
     }
     *bugs = next_bugs;
 }
 
 #[aoc(day24, part1)]
-pub fn solve_day24_part1(input: &[[Tile; 5]; 5]) -> usize {
-    let mut bugs = *input;
+pub fn solve_day24_part1(input: &Grid<Tile>) -> usize {
+    let mut bugs = input.clone();
     let mut hist = HashSet::new();
-    hist.insert(bugs);
+    hist.insert(bugs.clone());
     loop {
         step(&mut bugs);
-        if !hist.insert(bugs) {
+        if !hist.insert(bugs.clone()) {
             // Break if we've seen this state before
             break;
         }
     }
 
     bugs.iter()
-        .flatten()
         .enumerate()
-        .filter_map(|(n, tile)| match tile {
+        .filter_map(|(n, (_, tile))| match tile {
             Tile::Bug => Some(1 << n),
             Tile::Empty => None,
         })
         .sum()
 }
 
-fn step_recursive(bugs: &mut HashMap<i32, [[Tile; 5]; 5]>) {
+fn step_recursive(bugs: &mut HashMap<i32, Grid<Tile>>) {
     let mut next_bugs = bugs.clone();
     for (level, grid) in bugs.iter() {
         for y in 0..5 {
@@ -90,21 +70,21 @@ fn step_recursive(bugs: &mut HashMap<i32, [[Tile; 5]; 5]>) {
                     if y == 0 {
                         // check 2,1 on previous level
                         if let Some(prev_grid) = bugs.get(&(level - 1)) {
-                            if prev_grid[1][2] == Tile::Bug {
+                            if prev_grid[(2, 1)] == Tile::Bug {
                                 count += 1;
                             }
                         }
                     } else if y == 3 && x == 2 {
                         // check bottom row of next level
                         if let Some(next_grid) = bugs.get(&(level + 1)) {
-                            for tile in next_grid[4].iter() {
+                            for tile in next_grid.row(4).iter() {
                                 if *tile == Tile::Bug {
                                     count += 1;
                                 }
                             }
                         }
                     } else {
-                        if grid[y - 1][x] == Tile::Bug {
+                        if grid[(x, y - 1)] == Tile::Bug {
                             count += 1;
                         }
                     }
@@ -112,21 +92,21 @@ fn step_recursive(bugs: &mut HashMap<i32, [[Tile; 5]; 5]>) {
                     if y == 4 {
                         // check 2,3 on previous level
                         if let Some(prev_grid) = bugs.get(&(level - 1)) {
-                            if prev_grid[3][2] == Tile::Bug {
+                            if prev_grid[(2, 3)] == Tile::Bug {
                                 count += 1;
                             }
                         }
                     } else if y == 1 && x == 2 {
                         // check top row of next level
                         if let Some(next_grid) = bugs.get(&(level + 1)) {
-                            for tile in next_grid[0].iter() {
+                            for tile in next_grid.row(0).iter() {
                                 if *tile == Tile::Bug {
                                     count += 1;
                                 }
                             }
                         }
                     } else {
-                        if grid[y + 1][x] == Tile::Bug {
+                        if grid[(x, y + 1)] == Tile::Bug {
                             count += 1;
                         }
                     }
@@ -134,21 +114,21 @@ fn step_recursive(bugs: &mut HashMap<i32, [[Tile; 5]; 5]>) {
                     if x == 0 {
                         // check 1,2 on previous level
                         if let Some(prev_grid) = bugs.get(&(level - 1)) {
-                            if prev_grid[2][1] == Tile::Bug {
+                            if prev_grid[(1, 2)] == Tile::Bug {
                                 count += 1;
                             }
                         }
                     } else if y == 2 && x == 3 {
                         // check right row of next level
                         if let Some(next_grid) = bugs.get(&(level + 1)) {
-                            for tile in next_grid.iter() {
-                                if tile[4] == Tile::Bug {
+                            for y in 0..5 {
+                                if next_grid[(4, y)] == Tile::Bug {
                                     count += 1;
                                 }
                             }
                         }
                     } else {
-                        if grid[y][x - 1] == Tile::Bug {
+                        if grid[(x - 1, y)] == Tile::Bug {
                             count += 1;
                         }
                     }
@@ -156,35 +136,39 @@ fn step_recursive(bugs: &mut HashMap<i32, [[Tile; 5]; 5]>) {
                     if x == 4 {
                         // check 3,2 on previous level
                         if let Some(prev_grid) = bugs.get(&(level - 1)) {
-                            if prev_grid[2][3] == Tile::Bug {
+                            if prev_grid[(3, 2)] == Tile::Bug {
                                 count += 1;
                             }
                         }
                     } else if y == 2 && x == 1 {
                         // check left row of next level
                         if let Some(next_grid) = bugs.get(&(level + 1)) {
-                            for tile in next_grid.iter() {
-                                if tile[0] == Tile::Bug {
+                            for y in 0..5 {
+                                if next_grid[(0, y)] == Tile::Bug {
                                     count += 1;
                                 }
                             }
                         }
                     } else {
-                        if grid[y][x + 1] == Tile::Bug {
+                        if grid[(x + 1, y)] == Tile::Bug {
                             count += 1;
                         }
                     }
                     count
                 };
-                match (grid[y][x], neighbor_count) {
+                match (grid[(x, y)], neighbor_count) {
                     (Tile::Empty, 1) | (Tile::Empty, 2) => {
-                        next_bugs.get_mut(level).unwrap()[y][x] = Tile::Bug;
+                        next_bugs.get_mut(level).unwrap()[(x, y)] = Tile::Bug;
                         // Ensure that neighboring levels are tracked
-                        next_bugs.entry(level - 1).or_insert([[Tile::Empty; 5]; 5]);
-                        next_bugs.entry(level + 1).or_insert([[Tile::Empty; 5]; 5]);
+                        next_bugs
+                            .entry(level - 1)
+                            .or_insert_with(|| Grid::new(5, 5, Tile::Empty));
+                        next_bugs
+                            .entry(level + 1)
+                            .or_insert_with(|| Grid::new(5, 5, Tile::Empty));
                     }
                     (Tile::Bug, count) if count != 1 => {
-                        next_bugs.get_mut(level).unwrap()[y][x] = Tile::Empty
+                        next_bugs.get_mut(level).unwrap()[(x, y)] = Tile::Empty
                     }
                     _ => (),
                 }
@@ -195,21 +179,17 @@ fn step_recursive(bugs: &mut HashMap<i32, [[Tile; 5]; 5]>) {
 }
 
 #[aoc(day24, part2)]
-pub fn solve_day24_part2(input: &[[Tile; 5]; 5]) -> usize {
+pub fn solve_day24_part2(input: &Grid<Tile>) -> usize {
     let mut bugs = HashMap::new();
-    bugs.insert(0, *input);
-    let zero_grid = [[Tile::Empty; 5]; 5];
-    bugs.insert(-1, zero_grid);
+    bugs.insert(0, input.clone());
+    let zero_grid = Grid::new(5, 5, Tile::Empty);
+    bugs.insert(-1, zero_grid.clone());
     bugs.insert(1, zero_grid);
     for _ in 0..200 {
         step_recursive(&mut bugs);
     }
     bugs.iter().fold(0, |acc, (_, grid)| {
-        acc + grid
-            .iter()
-            .flatten()
-            .filter(|tile| **tile == Tile::Bug)
-            .count()
+        acc + grid.iter().filter(|(_, tile)| **tile == Tile::Bug).count()
     })
 }
 
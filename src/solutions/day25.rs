@@ -1,8 +1,8 @@
-use crate::intcode;
+use crate::intcode::transcript;
+use crate::intcode::{Machine, Status};
+use crate::options;
 use aoc_runner_derive::{aoc, aoc_generator};
-use std::collections::VecDeque;
-use std::sync::mpsc::sync_channel;
-use std::thread;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 
 #[aoc_generator(day25)]
 pub fn parse_day25(input: &str) -> Vec<i64> {
@@ -12,8 +12,20 @@ pub fn parse_day25(input: &str) -> Vec<i64> {
         .collect()
 }
 
-#[aoc(day25, part1)]
-pub fn solve_day25_part1(program: &[i64]) -> i64 {
+// The password is only ever given as text, in the last thing the program says before halting
+fn find_password<'a>(lines: impl Iterator<Item = &'a str>) -> Option<i64> {
+    lines
+        .filter_map(|line| {
+            line.split(|ch: char| !ch.is_ascii_digit())
+                .find(|word| !word.is_empty())
+                .map(|word| word.parse().unwrap())
+        })
+        .last()
+}
+
+//#[aoc(day25, part1, interactive)]
+#[allow(dead_code)]
+pub fn solve_day25_part1_interactive(program: &[i64]) -> i64 {
     const COMMANDS: &str = "north\n\
                             west\n\
                             take antenna\n\
@@ -30,42 +42,511 @@ pub fn solve_day25_part1(program: &[i64]) -> i64 {
                             north\n\
                             east\n\
                             east\n";
-    let (tx_in, rx_in) = sync_channel(0);
-    let (tx_out, rx_out) = sync_channel(0);
-    let (tx_req, rx_req) = sync_channel(0);
-    let program = program.to_vec();
-    thread::spawn(move || intcode::execute_threaded(program, rx_in, tx_out, Some(&tx_req)));
-
-    let mut input_buffer = VecDeque::new();
-    for ch in COMMANDS.chars() {
-        input_buffer.push_back(ch);
-    }
-    loop {
-        match rx_req.try_recv() {
-            Ok(()) => {
-                tx_in
-                    .send({
-                        if let Some(ch) = input_buffer.pop_front() {
-                            ch as i64
-                        } else {
-                            let mut input = String::new();
-                            std::io::stdin().read_line(&mut input).unwrap();
-                            for ch in input.chars() {
-                                input_buffer.push_back(ch);
-                            }
-                            input_buffer.pop_front().unwrap() as i64
+    let (_, transcript) = transcript::interactive_session(program, 25, COMMANDS);
+    find_password(transcript.outputs()).expect("ERROR: the session ended without a password")
+}
+
+// No single command in the real game comes close to this, but the infinite loop never stops
+const MAX_STEPS: usize = 1_000_000;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Room {
+    pub name: String,
+    pub description: String,
+    pub doors: Vec<String>,
+    pub items: Vec<String>,
+}
+
+// Read every room described in some output, each of which looks like this:
+//
+// == Hull Breach ==
+// You got in through a hole in the floor here.
+//
+// Doors here lead:
+// - north
+//
+// Items here:
+// - mug
+//
+// Command?
+fn parse_rooms(output: &str) -> Vec<Room> {
+    let lines = output.lines().collect::<Vec<_>>();
+    let headers = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| line.starts_with("== ") && line.ends_with(" =="))
+        .map(|(i, _)| i)
+        .chain(std::iter::once(lines.len()))
+        .collect::<Vec<_>>();
+    headers
+        .windows(2)
+        .map(|bounds| {
+            let mut room = Room {
+                name: lines[bounds[0]]
+                    .trim_matches(|ch| ch == '=' || ch == ' ')
+                    .to_owned(),
+                description: String::new(),
+                doors: Vec::new(),
+                items: Vec::new(),
+            };
+            let mut lines = lines[bounds[0] + 1..bounds[1]].iter();
+            room.description = lines
+                .by_ref()
+                .take_while(|line| !line.is_empty())
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(" ");
+            let mut list = None;
+            for line in lines {
+                match *line {
+                    "Doors here lead:" => list = Some(&mut room.doors),
+                    "Items here:" => list = Some(&mut room.items),
+                    line if line.starts_with("- ") => {
+                        if let Some(list) = &mut list {
+                            list.push(line[2..].to_owned());
                         }
-                    })
-                    .expect("ERROR: failed to send input");
+                    }
+                    _ => list = None,
+                }
             }
-            _ => (),
+            room
+        })
+        .collect()
+}
+
+// The room the droid ended up in
+fn parse_room(output: &str) -> Option<Room> {
+    parse_rooms(output).pop()
+}
+
+#[derive(Clone)]
+struct Droid {
+    machine: Machine,
+}
+
+impl Droid {
+    fn new(program: &[i64]) -> (Self, String) {
+        let mut droid = Droid {
+            machine: Machine::new(program),
         };
-        match rx_out.try_recv() {
-            Ok(out) if 0 <= out && out < 128 => {
-                print!("{}", std::char::from_u32(out as u32).unwrap());
+        let (_, output) = droid.run(VecDeque::new());
+        (droid, output)
+    }
+
+    fn command(&mut self, command: &str) -> (Status, String) {
+        self.run(command.bytes().chain(Some(b'\n')).map(i64::from).collect())
+    }
+
+    fn run(&mut self, mut input: VecDeque<i64>) -> (Status, String) {
+        let mut output = Vec::new();
+        let status = self.machine.run(&mut input, &mut output, MAX_STEPS);
+        (status, output.iter().map(|ch| *ch as u8 as char).collect())
+    }
+}
+
+#[derive(Default)]
+struct ShipMap {
+    rooms: BTreeMap<String, Room>,
+    // Which room each door of each room leads to
+    exits: HashMap<(String, String), String>,
+    dangerous: HashSet<String>,
+    // The room before the pressure-sensitive floor and the door leading onto it
+    checkpoint: Option<(String, String)>,
+}
+
+impl ShipMap {
+    // The doors to go through to get from one room to another, found with a bfs
+    fn route(&self, from: &str, to: &str) -> Vec<String> {
+        let mut previous = HashMap::new();
+        let mut queue = VecDeque::new();
+        queue.push_back(from.to_owned());
+        while let Some(current) = queue.pop_front() {
+            if current == to {
+                break;
+            }
+            for door in self.rooms[&current].doors.iter() {
+                if let Some(next) = self.exits.get(&(current.clone(), door.clone())) {
+                    if next != from && !previous.contains_key(next) {
+                        previous.insert(next.clone(), (current.clone(), door.clone()));
+                        queue.push_back(next.clone());
+                    }
+                }
             }
-            Ok(out) => return out,
-            _ => (),
         }
+        let mut route = Vec::new();
+        let mut current = to.to_owned();
+        while current != from {
+            let (room, door) = previous
+                .get(&current)
+                .unwrap_or_else(|| panic!("ERROR: no route from {} to {}", from, to));
+            route.push(door.clone());
+            current = room.clone();
+        }
+        route.reverse();
+        route
+    }
+}
+
+fn door_offset(door: &str) -> (i32, i32) {
+    match door {
+        "north" => (0, -1),
+        "south" => (0, 1),
+        "west" => (-1, 0),
+        "east" => (1, 0),
+        _ => panic!("ERROR: {} is not a valid door", door),
+    }
+}
+
+// Two letter names for the rooms, the initials of the first two words where possible. A name
+// without any letters is used as it is.
+fn abbreviations<'a>(names: impl Iterator<Item = &'a String>) -> HashMap<String, String> {
+    let mut abbreviations = HashMap::new();
+    let mut taken = HashSet::new();
+    for name in names {
+        let words = name
+            .split(|ch: char| !ch.is_ascii_alphabetic())
+            .filter(|word| !word.is_empty())
+            .map(|word| word.to_ascii_uppercase())
+            .collect::<Vec<_>>();
+        let letters = words.concat().chars().collect::<Vec<_>>();
+        if letters.is_empty() {
+            taken.insert(name.clone());
+            abbreviations.insert(name.clone(), name.clone());
+            continue;
+        }
+        let initials = words
+            .get(1)
+            .map(|second| format!("{}{}", letters[0], &second[..1]));
+        let abbreviation = initials
+            .into_iter()
+            .chain(
+                letters[1..]
+                    .iter()
+                    .map(|ch| format!("{}{}", letters[0], ch)),
+            )
+            .find(|abbreviation| !taken.contains(abbreviation))
+            .unwrap_or_else(|| format!("{}?", letters[0]));
+        taken.insert(abbreviation.clone());
+        abbreviations.insert(name.clone(), abbreviation);
+    }
+    abbreviations
+}
+
+impl ShipMap {
+    // Place the rooms on a grid by following doors out from `start`. When the spot next to a room
+    // is already taken by another room or corridor, a new row or column is opened up beside it,
+    // stretching every corridor that crosses it.
+    fn layout(&self, start: &str) -> HashMap<String, (i32, i32)> {
+        let mut positions = HashMap::new();
+        let mut corridors: Vec<(String, String)> = Vec::new();
+        let mut queue = VecDeque::new();
+        positions.insert(start.to_owned(), (0, 0));
+        queue.push_back(start.to_owned());
+        while let Some(current) = queue.pop_front() {
+            let (x, y) = positions[&current];
+            for door in self.rooms[&current].doors.iter() {
+                let next = match self.exits.get(&(current.clone(), door.clone())) {
+                    Some(next) if !positions.contains_key(next) => next,
+                    _ => continue,
+                };
+                let (dx, dy) = door_offset(door);
+                let target = (x + dx, y + dy);
+                let blocked = positions.values().any(|position| *position == target)
+                    || corridors.iter().any(|(a, b)| {
+                        let (a, b) = (positions[a], positions[b]);
+                        let between = |p: i32, a: i32, b: i32| a.min(b) < p && p < a.max(b);
+                        a.1 == b.1 && a.1 == target.1 && between(target.0, a.0, b.0)
+                            || a.0 == b.0 && a.0 == target.0 && between(target.1, a.1, b.1)
+                    });
+                if blocked {
+                    for position in positions.values_mut() {
+                        if (position.0 - x) * dx > 0 {
+                            position.0 += dx;
+                        }
+                        if (position.1 - y) * dy > 0 {
+                            position.1 += dy;
+                        }
+                    }
+                }
+                positions.insert(next.clone(), target);
+                corridors.push((current.clone(), next.clone()));
+                queue.push_back(next.clone());
+            }
+        }
+        positions
+    }
+
+    // An ASCII drawing of the ship like the one in notes/day25.txt, followed by a key of room names
+    // and the items in each room
+    fn render(&self, start: &str) -> String {
+        let positions = self.layout(start);
+        let abbreviations = abbreviations(self.rooms.keys());
+        let min_x = positions.values().map(|(x, _)| *x).min().unwrap();
+        let max_x = positions.values().map(|(x, _)| *x).max().unwrap();
+        let min_y = positions.values().map(|(_, y)| *y).min().unwrap();
+        let max_y = positions.values().map(|(_, y)| *y).max().unwrap();
+        // Each room takes up two characters with corridors in between
+        let to_canvas =
+            |(x, y): (i32, i32)| (((x - min_x) * 5) as usize, ((y - min_y) * 2) as usize);
+        let mut canvas =
+            vec![vec![' '; ((max_x - min_x) * 5 + 2) as usize]; ((max_y - min_y) * 2 + 1) as usize];
+        let mut draw = |col: usize, row: usize, ch: char| {
+            canvas[row][col] = match canvas[row][col] {
+                ' ' => ch,
+                '-' | '|' if canvas[row][col] != ch => '+',
+                other => other,
+            }
+        };
+
+        for ((room, _), next) in self.exits.iter() {
+            let (col_a, row_a) = to_canvas(positions[room]);
+            let (col_b, row_b) = to_canvas(positions[next]);
+            if row_a == row_b {
+                for col in col_a.min(col_b) + 3..col_a.max(col_b) - 1 {
+                    draw(col, row_a, '-');
+                }
+            } else if col_a == col_b {
+                for row in row_a.min(row_b) + 1..row_a.max(row_b) {
+                    draw(col_a, row, '|');
+                }
+            }
+        }
+        for (room, position) in positions.iter() {
+            let (col, row) = to_canvas(*position);
+            for (i, ch) in abbreviations[room].chars().take(2).enumerate() {
+                canvas[row][col + i] = ch;
+            }
+        }
+
+        let mut rendered = canvas
+            .iter()
+            .map(|row| row.iter().collect::<String>().trim_end().to_owned())
+            .collect::<Vec<_>>();
+        rendered.push(String::new());
+        for room in self.rooms.values() {
+            rendered.push(format!("{} - {}", abbreviations[&room.name], room.name));
+        }
+        let width = self
+            .rooms
+            .values()
+            .flat_map(|room| room.items.iter().map(String::len))
+            .max()
+            .unwrap_or(0);
+        rendered.push(String::new());
+        for room in self.rooms.values() {
+            for item in room.items.iter() {
+                let mut line = format!("{} - {:2$}", abbreviations[&room.name], item, width);
+                if self.dangerous.contains(item) {
+                    line.push_str(" - BAD");
+                }
+                rendered.push(line.trim_end().to_owned());
+            }
+        }
+        rendered.join("\n")
+    }
+
+    // The ship as a Graphviz graph, with doors as edges labeled by direction from the first room
+    fn to_dot(&self) -> String {
+        let mut dot = "graph ship {\n".to_owned();
+        for room in self.rooms.values() {
+            let mut label = room.name.clone();
+            for item in room.items.iter() {
+                label.push_str("\\n");
+                label.push_str(item);
+                if self.dangerous.contains(item) {
+                    label.push_str(" (bad)");
+                }
+            }
+            dot.push_str(&format!("    \"{}\" [label=\"{}\"];\n", room.name, label));
+        }
+        let mut drawn = HashSet::new();
+        for room in self.rooms.values() {
+            for door in room.doors.iter() {
+                if let Some(next) = self.exits.get(&(room.name.clone(), door.clone())) {
+                    if drawn.insert((next.clone(), room.name.clone())) {
+                        drawn.insert((room.name.clone(), next.clone()));
+                        dot.push_str(&format!(
+                            "    \"{}\" -- \"{}\" [label=\"{}\"];\n",
+                            room.name, next, door
+                        ));
+                    }
+                }
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+// Try picking an item up with a copy of the droid. Bad items end the game, never return control or
+// stop the droid from moving.
+fn is_dangerous(droid: &Droid, room: &Room, item: &str) -> bool {
+    let mut droid = droid.clone();
+    if droid.command(&format!("take {}", item)).0 != Status::NeedsInput {
+        return true;
+    }
+    let (status, output) = droid.command(&room.doors[0]);
+    status != Status::NeedsInput || parse_room(&output).is_none()
+}
+
+// This is a dfs, using a copy of the droid for every door instead of walking back
+fn explore(droid: &Droid, room: Room, map: &mut ShipMap) {
+    for item in room.items.iter() {
+        if is_dangerous(droid, &room, item) {
+            map.dangerous.insert(item.clone());
+        }
+    }
+    map.rooms.insert(room.name.clone(), room.clone());
+    for door in room.doors.iter() {
+        let mut next_droid = droid.clone();
+        let (_, output) = next_droid.command(door);
+        let mut rooms = parse_rooms(&output);
+        let next_room = rooms.pop().expect("ERROR: failed to read room");
+        if next_room.name == room.name {
+            // Ejected back by the pressure-sensitive floor, which is described first
+            let floor = rooms.remove(0);
+            map.exits
+                .insert((room.name.clone(), door.clone()), floor.name.clone());
+            map.rooms.insert(floor.name.clone(), floor);
+            map.checkpoint = Some((room.name.clone(), door.clone()));
+            continue;
+        }
+        map.exits
+            .insert((room.name.clone(), door.clone()), next_room.name.clone());
+        if !map.rooms.contains_key(&next_room.name) {
+            explore(&next_droid, next_room, map);
+        }
+    }
+}
+
+// Try combinations of the carried items on the pressure-sensitive floor. If a set of items is too
+// heavy every set containing it is too and if it is too light so are all of its subsets.
+fn try_combinations(droid: &Droid, items: &[String], floor_door: &str) -> Option<i64> {
+    let mut too_heavy = Vec::new();
+    let mut too_light = Vec::new();
+    let mut subsets = (0..1_u32 << items.len()).collect::<Vec<_>>();
+    subsets.sort_by_key(|subset| subset.count_ones());
+    for subset in subsets {
+        if too_heavy.iter().any(|heavy| subset & heavy == *heavy)
+            || too_light.iter().any(|light| subset & light == subset)
+        {
+            continue;
+        }
+        let mut droid = droid.clone();
+        for (i, item) in items.iter().enumerate() {
+            if subset & 1 << i == 0 {
+                droid.command(&format!("drop {}", item));
+            }
+        }
+        let (_, output) = droid.command(floor_door);
+        if output.contains("Droids on this ship are lighter") {
+            too_heavy.push(subset);
+        } else if output.contains("Droids on this ship are heavier") {
+            too_light.push(subset);
+        } else {
+            return find_password(output.lines());
+        }
+    }
+    None
+}
+
+#[aoc(day25, part1, auto)]
+pub fn solve_day25_part1_auto(program: &[i64]) -> i64 {
+    let (mut droid, output) = Droid::new(program);
+    let start = parse_room(&output).expect("ERROR: failed to read starting room");
+    let mut map = ShipMap::default();
+    explore(&droid, start.clone(), &mut map);
+    let (checkpoint, floor_door) = map
+        .checkpoint
+        .clone()
+        .expect("ERROR: failed to find the security checkpoint");
+    options::print(25, "MAP", || format!("{}\n", map.render(&start.name)));
+    options::write(25, "DOT", || map.to_dot());
+
+    // Pick up every safe item and carry them all to the checkpoint
+    let mut current = start.name;
+    let mut carried = Vec::new();
+    for room in map.rooms.values() {
+        for item in room.items.iter() {
+            if map.dangerous.contains(item) {
+                continue;
+            }
+            for door in map.route(&current, &room.name) {
+                droid.command(&door);
+            }
+            current = room.name.clone();
+            droid.command(&format!("take {}", item));
+            carried.push(item.clone());
+        }
+    }
+    for door in map.route(&current, &checkpoint) {
+        droid.command(&door);
+    }
+
+    try_combinations(&droid, &carried, &floor_door).expect("ERROR: no combination of items works")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_room_test() {
+        const OUTPUT: &str = "\n\n\n== Hull Breach ==\n\
+                              You got in through a hole in the floor here. To keep your ship \
+                              from also freezing, the hole has been sealed.\n\
+                              \n\
+                              Doors here lead:\n\
+                              - north\n\
+                              - west\n\
+                              \n\
+                              Items here:\n\
+                              - mug\n\
+                              \n\
+                              Command?\n";
+        let room = parse_room(OUTPUT).unwrap();
+        assert_eq!(room.name, "Hull Breach");
+        assert!(room.description.starts_with("You got in"));
+        assert_eq!(room.doors, vec!["north", "west"]);
+        assert_eq!(room.items, vec!["mug"]);
+    }
+
+    #[test]
+    fn render_test() {
+        let room = |name: &str, doors: &[&str], items: &[&str]| Room {
+            name: name.to_owned(),
+            description: String::new(),
+            doors: doors.iter().map(|door| (*door).to_owned()).collect(),
+            items: items.iter().map(|item| (*item).to_owned()).collect(),
+        };
+        let mut map = ShipMap::default();
+        for room in vec![
+            room("Hull Breach", &["north"], &[]),
+            room("Sick Bay", &["south", "east"], &["hologram"]),
+            room("Stables", &["west"], &["infinite loop"]),
+        ] {
+            map.rooms.insert(room.name.clone(), room);
+        }
+        for (from, door, to) in vec![
+            ("Hull Breach", "north", "Sick Bay"),
+            ("Sick Bay", "south", "Hull Breach"),
+            ("Sick Bay", "east", "Stables"),
+            ("Stables", "west", "Sick Bay"),
+        ] {
+            map.exits
+                .insert((from.to_owned(), door.to_owned()), to.to_owned());
+        }
+        map.dangerous.insert("infinite loop".to_owned());
+        let rendered = map.render("Hull Breach");
+        let lines = rendered.lines().collect::<Vec<_>>();
+        assert_eq!(lines[..3], ["SB - ST", "|", "HB"]);
+        assert!(lines.contains(&"ST - infinite loop - BAD"));
+        let names = ["Hull Breach", "42", ""].map(str::to_owned);
+        let abbreviations = abbreviations(names.iter());
+        assert_eq!(abbreviations["42"], "42");
+        assert_eq!(abbreviations[""], "");
+        assert!(map
+            .to_dot()
+            .contains("\"Sick Bay\" -- \"Stables\" [label=\"east\"];"));
     }
 }
@@ -1,9 +1,15 @@
+use crate::grid::SparseGrid;
+use crate::options;
 use aoc_runner_derive::{aoc, aoc_generator};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::PathBuf;
 
 #[aoc_generator(day3)]
 pub fn parse_day3(input: &str) -> Vec<Vec<(char, i32)>> {
     input
         .lines()
+        .filter(|line| !line.is_empty())
         .map(|line| {
             line.split(',')
                 .map(|delta| {
@@ -18,117 +24,364 @@ pub fn parse_day3(input: &str) -> Vec<Vec<(char, i32)>> {
         .collect()
 }
 
-fn path_to_points(wire: &[(char, i32)]) -> Vec<(i32, i32)> {
+// A straight stretch of wire, either horizontal or vertical
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Segment {
+    start: (i32, i32),
+    end: (i32, i32),
+    // How many steps along the wire the start is
+    steps: i32,
+}
+
+impl Segment {
+    fn is_horizontal(&self) -> bool {
+        self.start.1 == self.end.1 && self.start.0 != self.end.0
+    }
+
+    // The steps along the wire to a point on this segment
+    fn steps_to(&self, point: (i32, i32)) -> i32 {
+        self.steps + (point.0 - self.start.0).abs() + (point.1 - self.start.1).abs()
+    }
+
+    fn x_range(&self) -> (i32, i32) {
+        (self.start.0.min(self.end.0), self.start.0.max(self.end.0))
+    }
+
+    fn y_range(&self) -> (i32, i32) {
+        (self.start.1.min(self.end.1), self.start.1.max(self.end.1))
+    }
+}
+
+fn wire_segments(wire: &[(char, i32)]) -> Vec<Segment> {
     let mut current = (0, 0);
+    let mut steps = 0;
     wire.iter()
-        .map(|(direction, distance)| {
+        .map(|&(direction, distance)| {
             let start = current;
             match direction {
-                'U' => {
-                    current.1 += distance;
-                    (1..=*distance)
-                        .map(|y| (start.0, start.1 + y))
-                        .collect::<Vec<_>>()
-                }
-                'D' => {
-                    current.1 -= distance;
-                    (1..=*distance)
-                        .map(|y| (start.0, start.1 - y))
-                        .collect::<Vec<_>>()
-                }
-                'L' => {
-                    current.0 -= distance;
-                    (1..=*distance)
-                        .map(|x| (start.0 - x, start.1))
-                        .collect::<Vec<_>>()
-                }
-                'R' => {
-                    current.0 += distance;
-                    (1..=*distance)
-                        .map(|x| (start.0 + x, start.1))
-                        .collect::<Vec<_>>()
-                }
-                _ => {
-                    panic!("ERROR: {} is not a valid direction", direction);
-                }
+                'U' => current.1 += distance,
+                'D' => current.1 -= distance,
+                'L' => current.0 -= distance,
+                'R' => current.0 += distance,
+                _ => panic!("ERROR: {} is not a valid direction", direction),
             }
+            let segment = Segment {
+                start,
+                end: current,
+                steps,
+            };
+            steps += distance;
+            segment
         })
-        .flatten()
         .collect()
 }
 
-#[aoc(day3, part1)]
-pub fn solve_day3_part1(input: &[Vec<(char, i32)>]) -> i32 {
-    let wire1 = path_to_points(&input[1]);
-    path_to_points(&input[0])
-        .iter()
-        .filter(|point0| wire1.iter().any(|point1| *point0 == point1))
-        .min_by(|point0, point1| {
-            (point0.0.abs() + point0.1.abs()).cmp(&(point1.0.abs() + point1.1.abs()))
-        })
-        .map(|point| point.0 + point.1)
-        .unwrap()
+// A point where two different wires meet, other than the central port
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Crossing {
+    // The indices of the two wires, lowest first
+    pub wires: (usize, usize),
+    pub point: (i32, i32),
+    // Manhattan distance from the central port
+    pub distance: i32,
+    // The steps both wires take to get here, added together
+    pub steps: i32,
 }
 
-fn path_to_points_and_steps(wire: &[(char, i32)]) -> Vec<(i32, i32, i32)> {
-    let mut current = (0, 0, 0);
-    wire.iter()
-        .map(|(direction, distance)| {
-            let start = current;
-            current.2 += distance;
-            match direction {
-                'U' => {
-                    current.1 += distance;
-                    (1..=*distance)
-                        .map(|y| (start.0, start.1 + y, start.2 + y))
-                        .collect::<Vec<_>>()
-                }
-                'D' => {
-                    current.1 -= distance;
-                    (1..=*distance)
-                        .map(|y| (start.0, start.1 - y, start.2 + y))
-                        .collect::<Vec<_>>()
-                }
-                'L' => {
-                    current.0 -= distance;
-                    (1..=*distance)
-                        .map(|x| (start.0 - x, start.1, start.2 + x))
-                        .collect::<Vec<_>>()
+impl Crossing {
+    fn new(a: (usize, &Segment), b: (usize, &Segment), point: (i32, i32)) -> Self {
+        Crossing {
+            wires: (a.0.min(b.0), a.0.max(b.0)),
+            point,
+            distance: point.0.abs() + point.1.abs(),
+            steps: a.1.steps_to(point) + b.1.steps_to(point),
+        }
+    }
+}
+
+// Every crossing between different wires. Perpendicular segments are found by sweeping a vertical
+// line from left to right, keeping the horizontal segments it currently passes through ordered by
+// their height. Wires running along each other are checked separately, with every shared point
+// counting as a crossing.
+fn crossings(wires: &[Vec<Segment>]) -> Vec<Crossing> {
+    const ADD: u8 = 0;
+    const QUERY: u8 = 1;
+    const REMOVE: u8 = 2;
+
+    let segments = wires
+        .iter()
+        .enumerate()
+        .flat_map(|(wire, segments)| segments.iter().map(move |segment| (wire, segment)))
+        .collect::<Vec<_>>();
+
+    // Adding before querying before removing at the same x lets segments meet at their ends
+    let mut events = Vec::new();
+    for (index, (_, segment)) in segments.iter().enumerate() {
+        let (min_x, max_x) = segment.x_range();
+        if segment.is_horizontal() {
+            events.push((min_x, ADD, index));
+            events.push((max_x, REMOVE, index));
+        } else {
+            events.push((min_x, QUERY, index));
+        }
+    }
+    events.sort_unstable();
+
+    let mut result = Vec::new();
+    let mut active = BTreeMap::<i32, Vec<usize>>::new();
+    for (x, kind, index) in events {
+        let (wire, segment) = segments[index];
+        match kind {
+            ADD => active.entry(segment.start.1).or_default().push(index),
+            REMOVE => active
+                .get_mut(&segment.start.1)
+                .unwrap()
+                .retain(|&i| i != index),
+            _ => {
+                let (min_y, max_y) = segment.y_range();
+                for (&y, horizontal) in active.range(min_y..=max_y) {
+                    for &other in horizontal {
+                        let (other_wire, other_segment) = segments[other];
+                        if other_wire != wire && (x, y) != (0, 0) {
+                            result.push(Crossing::new(
+                                (wire, segment),
+                                (other_wire, other_segment),
+                                (x, y),
+                            ));
+                        }
+                    }
                 }
-                'R' => {
-                    current.0 += distance;
-                    (1..=*distance)
-                        .map(|x| (start.0 + x, start.1, start.2 + x))
-                        .collect::<Vec<_>>()
+            }
+        }
+    }
+
+    // Segments of different wires lying on the same line
+    let mut lines = HashMap::<(bool, i32), Vec<usize>>::new();
+    for (index, (_, segment)) in segments.iter().enumerate() {
+        let line = if segment.is_horizontal() {
+            (true, segment.start.1)
+        } else {
+            (false, segment.start.0)
+        };
+        lines.entry(line).or_default().push(index);
+    }
+    for ((horizontal, position), on_line) in lines {
+        let range = |segment: &Segment| {
+            if horizontal {
+                segment.x_range()
+            } else {
+                segment.y_range()
+            }
+        };
+        for (i, &a) in on_line.iter().enumerate() {
+            for &b in &on_line[i + 1..] {
+                let (wire_a, segment_a) = segments[a];
+                let (wire_b, segment_b) = segments[b];
+                if wire_a == wire_b {
+                    continue;
                 }
-                _ => {
-                    panic!("ERROR: {} is not a valid direction", direction);
+                let (min_a, max_a) = range(segment_a);
+                let (min_b, max_b) = range(segment_b);
+                for along in min_a.max(min_b)..=max_a.min(max_b) {
+                    let point = if horizontal {
+                        (along, position)
+                    } else {
+                        (position, along)
+                    };
+                    if point != (0, 0) {
+                        result.push(Crossing::new(
+                            (wire_a, segment_a),
+                            (wire_b, segment_b),
+                            point,
+                        ));
+                    }
                 }
             }
+        }
+    }
+
+    // A point can be found once for each segment of a wire through it, and a wire coming back to a
+    // point gets there in more steps the second time. Only the first visit counts, and sorting puts
+    // the fewest steps first for each pair of wires and point.
+    result.sort_unstable();
+    result.dedup_by_key(|crossing| (crossing.wires, crossing.point));
+    result
+}
+
+fn day3_crossings(input: &[Vec<(char, i32)>]) -> Vec<Crossing> {
+    if input.len() < 2 {
+        panic!("ERROR: need at least two wires, only {} given", input.len());
+    }
+    let wires = input
+        .iter()
+        .map(|wire| wire_segments(wire))
+        .collect::<Vec<_>>();
+    crossings(&wires)
+}
+
+// One line for every crossing, grouped by the pair of wires
+fn crossing_report(crossings: &[Crossing]) -> String {
+    crossings
+        .iter()
+        .map(|crossing| {
+            format!(
+                "wires {} and {} cross at ({}, {}): distance {}, steps {}\n",
+                crossing.wires.0,
+                crossing.wires.1,
+                crossing.point.0,
+                crossing.point.1,
+                crossing.distance,
+                crossing.steps
+            )
         })
-        .flatten()
         .collect()
 }
+
+// The wires drawn the way the puzzle does, with the central port as 'o' and crossings as 'X'.
+// Every character covers a `scale` by `scale` square of the panel and up is up.
+fn render_text(wires: &[Vec<Segment>], crossings: &[Crossing], scale: i32) -> String {
+    let cell = |(x, y): (i32, i32)| {
+        (
+            i64::from(x.div_euclid(scale)),
+            i64::from((-y).div_euclid(scale)),
+        )
+    };
+    let mut panel = SparseGrid::new();
+    for segment in wires.iter().flatten() {
+        let (dx, dy) = (
+            (segment.end.0 - segment.start.0).signum(),
+            (segment.end.1 - segment.start.1).signum(),
+        );
+        let line = if segment.is_horizontal() { '-' } else { '|' };
+        let mut point = segment.start;
+        while point != segment.end {
+            point = (point.0 + dx, point.1 + dy);
+            if panel.get(cell(point)) != Some(&'+') {
+                panel.insert(cell(point), line);
+            }
+        }
+        panel.insert(cell(segment.end), '+');
+    }
+    for crossing in crossings {
+        panel.insert(cell(crossing.point), 'X');
+    }
+    panel.insert(cell((0, 0)), 'o');
+    panel.render(' ', |_, &ch| ch)
+}
+
+const COLORS: [&str; 6] = [
+    "#d62728", "#1f77b4", "#2ca02c", "#ff7f0e", "#9467bd", "#8c564b",
+];
+
+// The wires as an SVG image, each in its own color, with the crossings circled
+fn render_svg(wires: &[Vec<Segment>], crossings: &[Crossing]) -> String {
+    let points = wires.iter().flatten().map(|segment| segment.end);
+    let (min_x, min_y, max_x, max_y) =
+        points.fold((0, 0, 0, 0), |(min_x, min_y, max_x, max_y), (x, y)| {
+            (min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y))
+        });
+    let size = (max_x - min_x).max(max_y - min_y).max(1);
+    let margin = size / 20 + 1;
+    let radius = size / 200 + 1;
+    let mut out = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\">\n",
+        min_x - margin,
+        -max_y - margin,
+        max_x - min_x + 2 * margin,
+        max_y - min_y + 2 * margin
+    );
+    for (index, wire) in wires.iter().enumerate() {
+        let points = Some((0, 0))
+            .into_iter()
+            .chain(wire.iter().map(|segment| segment.end))
+            .map(|(x, y)| format!("{},{}", x, -y))
+            .collect::<Vec<_>>();
+        out += &format!(
+            "<polyline fill=\"none\" stroke=\"{}\" stroke-width=\"1\" \
+             vector-effect=\"non-scaling-stroke\" points=\"{}\"/>\n",
+            COLORS[index % COLORS.len()],
+            points.join(" ")
+        );
+    }
+    for crossing in crossings {
+        out += &format!(
+            "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"none\" stroke=\"black\" \
+             vector-effect=\"non-scaling-stroke\"/>\n",
+            crossing.point.0, -crossing.point.1, radius
+        );
+    }
+    out += &format!(
+        "<circle cx=\"0\" cy=\"0\" r=\"{}\" fill=\"black\"/>\n",
+        radius
+    );
+    out += "</svg>\n";
+    out
+}
+
+// Text renders are scaled down to about this many characters across
+const TEXT_WIDTH: i32 = 200;
+
+enum Render {
+    Text(PathBuf),
+    Svg(PathBuf),
+}
+
+impl Render {
+    // Read from AOC_DAY3_RENDER, which is either "text:<file>" or "svg:<file>"
+    fn from_env() -> Option<Self> {
+        let value = options::get(3, "RENDER")?;
+        if let Some(path) = value.strip_prefix("text:") {
+            Some(Render::Text(PathBuf::from(path)))
+        } else if let Some(path) = value.strip_prefix("svg:") {
+            Some(Render::Svg(PathBuf::from(path)))
+        } else {
+            panic!("ERROR: unknown AOC_DAY3_RENDER output {}", value);
+        }
+    }
+
+    fn write(&self, input: &[Vec<(char, i32)>], crossings: &[Crossing]) {
+        let wires = input
+            .iter()
+            .map(|wire| wire_segments(wire))
+            .collect::<Vec<_>>();
+        let (path, contents) = match self {
+            Render::Text(path) => {
+                let (min_x, max_x) = wires
+                    .iter()
+                    .flatten()
+                    .fold((0, 0), |(min_x, max_x), segment| {
+                        (min_x.min(segment.end.0), max_x.max(segment.end.0))
+                    });
+                let scale = ((max_x - min_x) / TEXT_WIDTH).max(1);
+                (path, render_text(&wires, crossings, scale))
+            }
+            Render::Svg(path) => (path, render_svg(&wires, crossings)),
+        };
+        fs::write(path, contents).expect("ERROR: failed to write the wire render");
+    }
+}
+
+#[aoc(day3, part1)]
+pub fn solve_day3_part1(input: &[Vec<(char, i32)>]) -> i32 {
+    let crossings = day3_crossings(input);
+    options::print(3, "REPORT", || crossing_report(&crossings));
+    if let Some(render) = Render::from_env() {
+        render.write(input, &crossings);
+    }
+    crossings
+        .iter()
+        .map(|crossing| crossing.distance)
+        .min()
+        .expect("ERROR: the wires never cross")
+}
+
 #[aoc(day3, part2)]
 pub fn solve_day3_part2(input: &[Vec<(char, i32)>]) -> i32 {
-    let wire1 = path_to_points_and_steps(&input[1]);
-    path_to_points_and_steps(&input[0])
+    day3_crossings(input)
         .iter()
-        .filter_map(|point0| {
-            if let Some(point1) = wire1
-                .iter()
-                .find(|point1| point0.0 == point1.0 && point0.1 == point1.1)
-            {
-                Some((point0, point1))
-            } else {
-                None
-            }
-        })
-        .min_by(|(point0a, point1a), (point0b, point1b)| {
-            (point0a.2 + point1a.2).cmp(&(point0b.2 + point1b.2))
-        })
-        .map(|(point0, point1)| point0.2 + point1.2)
-        .unwrap()
+        .map(|crossing| crossing.steps)
+        .min()
+        .expect("ERROR: the wires never cross")
 }
 
 #[cfg(test)]
@@ -169,4 +422,74 @@ mod tests {
     fn part2_example3() {
         assert_eq!(solve_day3_part2(&parse_day3(EXAMPLE_3)), 410);
     }
+
+    #[test]
+    fn day3_crossings_along_and_across() {
+        // The second wire runs along the first for three points, one of them a corner of the
+        // first, then crosses it going up
+        let wires = parse_day3("R4,U2,R2\nU2,R2,D2,R3,U4");
+        let crossings = day3_crossings(&wires);
+        let points = crossings
+            .iter()
+            .map(|crossing| crossing.point)
+            .collect::<Vec<_>>();
+        assert_eq!(points, [(2, 0), (3, 0), (4, 0), (5, 2)]);
+        assert!(crossings.iter().all(|crossing| crossing.wires == (0, 1)));
+        assert_eq!(crossings[0].steps, 2 + 6);
+        assert_eq!((crossings[3].distance, crossings[3].steps), (7, 7 + 11));
+    }
+
+    #[test]
+    fn day3_self_revisiting_wire() {
+        // The first wire comes back through (2, 0) going down, where the second wire ends
+        let wires = parse_day3("R4,U2,L2,D4\nU1,R2,D1");
+        let crossings = day3_crossings(&wires)
+            .iter()
+            .map(|crossing| (crossing.point, crossing.steps))
+            .collect::<Vec<_>>();
+        assert_eq!(crossings, [((2, 0), 2 + 4), ((2, 1), 9 + 3)]);
+    }
+
+    #[test]
+    fn day3_three_wires() {
+        let wires = parse_day3("R8,U5,L5,D3\nU7,R6,D4,L4\nD1,R5,U9");
+        let crossings = day3_crossings(&wires);
+        assert_eq!(
+            crossing_report(&crossings),
+            "wires 0 and 1 cross at (3, 3): distance 6, steps 40\n\
+             wires 0 and 1 cross at (6, 5): distance 11, steps 30\n\
+             wires 0 and 2 cross at (5, 0): distance 5, steps 12\n\
+             wires 0 and 2 cross at (5, 5): distance 10, steps 28\n\
+             wires 1 and 2 cross at (5, 3): distance 8, steps 28\n\
+             wires 1 and 2 cross at (5, 7): distance 12, steps 26\n"
+        );
+        assert_eq!(solve_day3_part2(&wires), 12);
+    }
+
+    #[test]
+    fn day3_render() {
+        let input = parse_day3(EXAMPLE_1);
+        let wires = input
+            .iter()
+            .map(|wire| wire_segments(wire))
+            .collect::<Vec<_>>();
+        let crossings = day3_crossings(&input);
+        assert_eq!(
+            render_text(&wires, &crossings, 1),
+            "+-----+  \n\
+             |     |  \n\
+             |  +--X-+\n\
+             |  |  | |\n\
+             | +X--+ |\n\
+             |  +    |\n\
+             |       |\n\
+             o-------+\n"
+        );
+        let svg = render_svg(&wires, &crossings);
+        assert!(
+            svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"-1 -8 10 9\">")
+        );
+        assert!(svg.contains("points=\"0,0 8,0 8,-5 3,-5 3,-2\""));
+        assert_eq!(svg.matches("<circle").count(), 3);
+    }
 }
@@ -1,72 +1,467 @@
 use aoc_runner_derive::{aoc, aoc_generator};
+use std::collections::HashMap;
+use std::fmt;
 
 #[aoc_generator(day4)]
-pub fn parse_day1(input: &str) -> (u32, u32) {
+pub fn parse_day1(input: &str) -> (u64, u64) {
     let mut it = input.split('-').map(|x| x.parse().unwrap());
     (it.next().unwrap(), it.next().unwrap())
 }
 
-#[aoc(day4, part1)]
-pub fn solve_day4_part1(input: &(u32, u32)) -> usize {
-    (input.0..=input.1)
-        .map(|number| {
-            number
-                .to_string()
-                .chars()
-                .map(|x| x.to_digit(10).unwrap())
-                .collect::<Vec<_>>()
-        })
-        .filter(|digits| {
-            digits.iter().is_sorted() && digits.windows(2).any(|pair| pair[0] == pair[1])
-        })
-        .count()
-}
-
-fn exactly_2_consecutive(digits: &[u32]) -> bool {
-    let mut consecutive_count = 1;
-    let mut previous = 10;
-    for digit in digits {
-        if *digit == previous {
-            consecutive_count += 1;
-        } else if consecutive_count == 2 {
-            return true;
+// A rule a password policy can require, checked against the digits of a candidate. Counting only
+// tells apart prefixes that differ in their last digit, the length of the run it ends and the
+// lengths of the runs before that, so anything else a rule looks at has to be checked by
+// `allows_prefix`.
+pub trait PasswordRule {
+    // How the rule is written in a policy
+    fn name(&self) -> String;
+
+    fn allows(&self, digits: &[u8]) -> bool;
+
+    // Why `digits` break the rule, only asked for digits it doesn't allow
+    fn reason(&self, digits: &[u8]) -> String;
+
+    // Whether some password starting with `prefix` could follow the rule. Prefixes are checked
+    // one digit longer each time, so only the last digit is new. Rules that can't tell until the
+    // password is complete allow every prefix.
+    fn allows_prefix(&self, _prefix: &[u8]) -> bool {
+        true
+    }
+}
+
+pub struct NonDecreasing;
+
+impl PasswordRule for NonDecreasing {
+    fn name(&self) -> String {
+        "non-decreasing".to_owned()
+    }
+
+    fn allows(&self, digits: &[u8]) -> bool {
+        digits.windows(2).all(|pair| pair[0] <= pair[1])
+    }
+
+    fn reason(&self, digits: &[u8]) -> String {
+        let i = digits
+            .windows(2)
+            .position(|pair| pair[0] > pair[1])
+            .unwrap()
+            + 1;
+        format!(
+            "the {} at position {} is smaller than the {} before it",
+            digits[i],
+            i,
+            digits[i - 1]
+        )
+    }
+
+    fn allows_prefix(&self, prefix: &[u8]) -> bool {
+        match prefix {
+            [.., previous, last] => previous <= last,
+            _ => true,
+        }
+    }
+}
+
+// The lengths of the runs of equal adjacent digits
+fn run_lengths(digits: &[u8]) -> impl Iterator<Item = usize> + '_ {
+    digits.chunk_by(|a, b| a == b).map(|run| run.len())
+}
+
+pub struct RunAtLeast(pub usize);
+
+impl PasswordRule for RunAtLeast {
+    fn name(&self) -> String {
+        format!("run>={}", self.0)
+    }
+
+    fn allows(&self, digits: &[u8]) -> bool {
+        run_lengths(digits).any(|length| length >= self.0)
+    }
+
+    fn reason(&self, _digits: &[u8]) -> String {
+        format!("no digit is repeated {} or more times", self.0)
+    }
+}
+
+pub struct RunExactly(pub usize);
+
+impl PasswordRule for RunExactly {
+    fn name(&self) -> String {
+        format!("run={}", self.0)
+    }
+
+    fn allows(&self, digits: &[u8]) -> bool {
+        run_lengths(digits).any(|length| length == self.0)
+    }
+
+    fn reason(&self, _digits: &[u8]) -> String {
+        format!("no digit is repeated exactly {} times", self.0)
+    }
+}
+
+// Only the given digits may be used
+pub struct Digits(pub Vec<u8>);
+
+impl PasswordRule for Digits {
+    fn name(&self) -> String {
+        let allowed = self.0.iter().map(u8::to_string).collect::<String>();
+        format!("digits={}", allowed)
+    }
+
+    fn allows(&self, digits: &[u8]) -> bool {
+        digits.iter().all(|digit| self.0.contains(digit))
+    }
+
+    fn reason(&self, digits: &[u8]) -> String {
+        let i = digits
+            .iter()
+            .position(|digit| !self.0.contains(digit))
+            .unwrap();
+        format!(
+            "the {} at position {} is not an allowed digit",
+            digits[i], i
+        )
+    }
+
+    fn allows_prefix(&self, prefix: &[u8]) -> bool {
+        prefix.last().is_none_or(|digit| self.0.contains(digit))
+    }
+}
+
+pub struct Length(pub usize);
+
+impl PasswordRule for Length {
+    fn name(&self) -> String {
+        format!("length={}", self.0)
+    }
+
+    fn allows(&self, digits: &[u8]) -> bool {
+        digits.len() == self.0
+    }
+
+    fn reason(&self, digits: &[u8]) -> String {
+        format!("{} digits long instead of {}", digits.len(), self.0)
+    }
+
+    fn allows_prefix(&self, prefix: &[u8]) -> bool {
+        prefix.len() <= self.0
+    }
+}
+
+// A rule some digits broke. The reason is only worked out when it is displayed.
+pub struct Failure<'a> {
+    rule: &'a dyn PasswordRule,
+    digits: &'a [u8],
+}
+
+impl fmt::Display for Failure<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.rule.name(), self.rule.reason(self.digits))
+    }
+}
+
+// A set of rules every password has to follow
+pub struct Policy {
+    rules: Vec<Box<dyn PasswordRule>>,
+}
+
+impl Policy {
+    // Parse a comma separated list of rules as written by `PasswordRule::name`
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let number = |value: &str| {
+            value
+                .parse()
+                .map_err(|_| format!("{:?} is not a number", value))
+        };
+        let rules = input
+            .split(',')
+            .map(|rule| -> Result<Box<dyn PasswordRule>, String> {
+                let rule = rule.trim();
+                if rule == "non-decreasing" {
+                    Ok(Box::new(NonDecreasing))
+                } else if let Some(length) = rule.strip_prefix("run>=") {
+                    Ok(Box::new(RunAtLeast(number(length)?)))
+                } else if let Some(length) = rule.strip_prefix("run=") {
+                    Ok(Box::new(RunExactly(number(length)?)))
+                } else if let Some(length) = rule.strip_prefix("length=") {
+                    Ok(Box::new(Length(number(length)?)))
+                } else if let Some(allowed) = rule.strip_prefix("digits=") {
+                    allowed
+                        .chars()
+                        .map(|ch| ch.to_digit(10).map(|digit| digit as u8))
+                        .collect::<Option<Vec<_>>>()
+                        .map(|allowed| Box::new(Digits(allowed)) as Box<dyn PasswordRule>)
+                        .ok_or_else(|| format!("{:?} are not all digits", allowed))
+                } else {
+                    Err(format!("unknown rule {:?}", rule))
+                }
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Policy { rules })
+    }
+
+    // Every rule `digits` break
+    pub fn explain<'a>(&'a self, digits: &'a [u8]) -> impl Iterator<Item = Failure<'a>> + 'a {
+        self.rules
+            .iter()
+            .filter(move |rule| !rule.allows(digits))
+            .map(move |rule| Failure {
+                rule: rule.as_ref(),
+                digits,
+            })
+    }
+}
+
+impl fmt::Display for Policy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names = self
+            .rules
+            .iter()
+            .map(|rule| rule.name())
+            .collect::<Vec<_>>();
+        write!(f, "{}", names.join(","))
+    }
+}
+
+fn length(number: u64) -> u32 {
+    number.to_string().len() as u32
+}
+
+// What counting can tell apart about a prefix: how many digits it has and how many are still to
+// come, its last digit, the length of the run that digit ends and a bit for every length of the
+// runs before it
+type Key = (usize, u32, Option<u8>, usize, u32);
+
+struct Counter<'a> {
+    low: u64,
+    high: u64,
+    policy: &'a Policy,
+    // The digits chosen so far, a u64 has at most 20
+    digits: [u8; 20],
+    // The counts for prefixes whose numbers are all in the range
+    memo: HashMap<Key, usize>,
+}
+
+impl Counter<'_> {
+    fn key(&self, filled: usize, remaining: u32) -> Key {
+        let digits = &self.digits[..filled];
+        let (mut last_run, mut earlier_runs) = (0, 0);
+        for length in run_lengths(digits) {
+            if last_run > 0 {
+                earlier_runs |= 1 << last_run;
+            }
+            last_run = length;
+        }
+        (
+            filled,
+            remaining,
+            digits.last().copied(),
+            last_run,
+            earlier_runs,
+        )
+    }
+
+    // Count the passwords starting with the first `filled` digits, which make up `prefix`, with
+    // `remaining` digits still to come. Prefixes that can't reach the range or that a rule rules
+    // out are cut off early. The bounds are worked out in u128 as they can be past u64::MAX when
+    // `high` has 20 digits.
+    fn count(&mut self, prefix: u64, filled: usize, remaining: u32) -> usize {
+        let scale = 10u128.pow(remaining);
+        let smallest = u128::from(prefix) * scale;
+        let largest = smallest + (scale - 1);
+        if largest < u128::from(self.low) || smallest > u128::from(self.high) {
+            return 0;
+        }
+        let digits = &self.digits[..filled];
+        if !self
+            .policy
+            .rules
+            .iter()
+            .all(|rule| rule.allows_prefix(digits))
+        {
+            return 0;
+        }
+        if remaining == 0 {
+            return self.policy.explain(digits).next().is_none() as usize;
+        }
+        // Once every number with the prefix is in the range the count only depends on the key,
+        // only the prefixes of `low` and `high` are ever left to count on their own
+        let inside = smallest >= u128::from(self.low) && largest <= u128::from(self.high);
+        let key = if inside {
+            let key = self.key(filled, remaining);
+            if let Some(&total) = self.memo.get(&key) {
+                return total;
+            }
+            Some(key)
         } else {
-            consecutive_count = 1;
+            None
+        };
+        // Only single digit numbers can start with a zero
+        let first = if filled == 0 && remaining > 1 { 1 } else { 0 };
+        let mut total = 0;
+        for digit in first..=9 {
+            self.digits[filled] = digit;
+            total += self.count(prefix * 10 + u64::from(digit), filled + 1, remaining - 1);
+        }
+        if let Some(key) = key {
+            self.memo.insert(key, total);
         }
-        previous = *digit;
+        total
     }
-    consecutive_count == 2
+}
+
+// How many numbers from `low` to `high` inclusive follow `policy`. The digits are chosen one at a
+// time and prefixes that end up in the same state are only counted once, so the work doesn't grow
+// with the size of the range.
+pub fn count_passwords(low: u64, high: u64, policy: &Policy) -> usize {
+    let mut counter = Counter {
+        low,
+        high,
+        policy,
+        digits: [0; 20],
+        memo: HashMap::new(),
+    };
+    (length(low)..=length(high))
+        .map(|digits| counter.count(0, 0, digits))
+        .sum()
+}
+
+const PART1_POLICY: &str = "length=6,non-decreasing,run>=2";
+const PART2_POLICY: &str = "length=6,non-decreasing,run=2";
+
+#[aoc(day4, part1)]
+pub fn solve_day4_part1(input: &(u64, u64)) -> usize {
+    count_passwords(input.0, input.1, &Policy::parse(PART1_POLICY).unwrap())
 }
 
 #[aoc(day4, part2)]
-pub fn solve_day4_part2(input: &(u32, u32)) -> usize {
-    (input.0..=input.1)
-        .map(|number| {
-            number
-                .to_string()
-                .chars()
-                .map(|x| x.to_digit(10).unwrap())
-                .collect::<Vec<_>>()
-        })
-        .filter(|digits| digits.iter().is_sorted() && exactly_2_consecutive(&digits))
-        .count()
+pub fn solve_day4_part2(input: &(u64, u64)) -> usize {
+    count_passwords(input.0, input.1, &Policy::parse(PART2_POLICY).unwrap())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn digits(number: u64) -> Vec<u8> {
+        number.to_string().bytes().map(|byte| byte - b'0').collect()
+    }
+
+    fn explain(policy: &Policy, number: u64) -> Vec<String> {
+        policy
+            .explain(&digits(number))
+            .map(|failure| failure.to_string())
+            .collect()
+    }
+
     #[test]
     fn exactly_2_consecutive_test1() {
-        assert_eq!(exactly_2_consecutive(&[1, 1, 2, 2, 3, 3]), true);
+        assert_eq!(RunExactly(2).allows(&[1, 1, 2, 2, 3, 3]), true);
     }
 
     #[test]
     fn exactly_2_consecutive_test2() {
-        assert_eq!(exactly_2_consecutive(&[1, 2, 3, 4, 4, 4]), false);
+        assert_eq!(RunExactly(2).allows(&[1, 2, 3, 4, 4, 4]), false);
     }
     #[test]
     fn exactly_2_consecutive_test3() {
-        assert_eq!(exactly_2_consecutive(&[1, 1, 1, 1, 2, 2]), true);
+        assert_eq!(RunExactly(2).allows(&[1, 1, 1, 1, 2, 2]), true);
+    }
+
+    #[test]
+    fn count_examples() {
+        let pair = Policy::parse(PART1_POLICY).unwrap();
+        assert_eq!(count_passwords(111111, 111111, &pair), 1);
+        assert_eq!(count_passwords(223450, 223450, &pair), 0);
+        assert_eq!(count_passwords(123789, 123789, &pair), 0);
+        let exact = Policy::parse(PART2_POLICY).unwrap();
+        assert_eq!(count_passwords(112233, 112233, &exact), 1);
+        assert_eq!(count_passwords(123444, 123444, &exact), 0);
+        assert_eq!(count_passwords(111122, 111122, &exact), 1);
+    }
+
+    // A custom run length rule, either a run of three or more or no pair at all
+    struct NoLonePair;
+
+    impl PasswordRule for NoLonePair {
+        fn name(&self) -> String {
+            "no-lone-pair".to_owned()
+        }
+
+        fn allows(&self, digits: &[u8]) -> bool {
+            run_lengths(digits).any(|length| length >= 3)
+                || run_lengths(digits).all(|length| length != 2)
+        }
+
+        fn reason(&self, _digits: &[u8]) -> String {
+            "there is a pair and no longer run".to_owned()
+        }
+    }
+
+    #[test]
+    fn count_matches_every_number() {
+        let policies = [
+            Policy {
+                rules: vec![Box::new(NonDecreasing), Box::new(NoLonePair)],
+            },
+            Policy::parse("run>=3,digits=1357").unwrap(),
+            Policy::parse("length=4,run=2").unwrap(),
+        ];
+        for policy in &policies {
+            let expected = (7..=12_345u64)
+                .filter(|&number| policy.explain(&digits(number)).next().is_none())
+                .count();
+            assert_eq!(count_passwords(7, 12_345, policy), expected, "{}", policy);
+        }
+    }
+
+    #[test]
+    fn count_twenty_digits() {
+        let pair = Policy::parse("non-decreasing,run>=2").unwrap();
+        let ones = 11_111_111_111_111_111_111;
+        assert_eq!(count_passwords(ones, ones, &pair), 1);
+        assert_eq!(count_passwords(u64::MAX - 1_000, u64::MAX, &pair), 0);
+        let twenty = Policy::parse("non-decreasing,run>=20").unwrap();
+        assert_eq!(count_passwords(ones, ones + 8, &twenty), 1);
+    }
+
+    #[test]
+    fn count_whole_range() {
+        // Every number below 10^15 with n digits and no two equal neighbours is one of 9^n
+        let pair = Policy::parse("run>=2").unwrap();
+        let top = 1_000_000_000_000_000;
+        let no_pair = (1..=15).map(|n| 9usize.pow(n)).sum::<usize>();
+        assert_eq!(count_passwords(1, top, &pair), top as usize - no_pair);
+    }
+
+    #[test]
+    fn policy_explains_failures() {
+        let policy = Policy::parse(PART2_POLICY).unwrap();
+        assert!(explain(&policy, 112233).is_empty());
+        assert_eq!(
+            explain(&policy, 123444),
+            ["run=2: no digit is repeated exactly 2 times"]
+        );
+        assert_eq!(
+            explain(&policy, 2234500),
+            [
+                "length=6: 7 digits long instead of 6",
+                "non-decreasing: the 0 at position 5 is smaller than the 5 before it",
+            ]
+        );
+    }
+
+    #[test]
+    fn policy_parse() {
+        let policy = Policy::parse("length=6, non-decreasing,run>=2").unwrap();
+        assert_eq!(policy.to_string(), "length=6,non-decreasing,run>=2");
+        let policy = Policy::parse("run>=3,digits=1357").unwrap();
+        assert_eq!(count_passwords(1, 1_000, &policy), 4);
+        assert_eq!(
+            explain(&policy, 1112).last().map(String::as_str),
+            Some("digits=1357: the 2 at position 3 is not an allowed digit")
+        );
+        assert!(Policy::parse("run>=two").is_err());
+        assert!(Policy::parse("palindrome").is_err());
     }
 }
@@ -1,43 +1,26 @@
+use crate::options;
+use crate::orbits::OrbitTree;
 use aoc_runner_derive::{aoc, aoc_generator};
-use std::collections::{HashMap, HashSet};
 
 #[aoc_generator(day6)]
-pub fn parse_day6(input: &str) -> HashMap<String, String> {
-    input
-        .lines()
-        .map(|x| {
-            let mut x = x.split(')');
-            let orbited = x.next().unwrap().to_owned();
-            let orbiter = x.next().unwrap().to_owned();
-            (orbiter, orbited)
-        })
-        .collect()
+pub fn parse_day6(input: &str) -> OrbitTree {
+    OrbitTree::parse(input).unwrap_or_else(|error| panic!("ERROR: invalid orbit map, {}", error))
 }
 
 #[aoc(day6, part1)]
-pub fn solve_day6_part1(orbits: &HashMap<String, String>) -> usize {
-    orbits.iter().fold(0, |mut count, (orbiter, _)| {
-        let mut current = orbiter;
-        while let Some(orbited) = orbits.get(current) {
-            current = orbited;
-            count += 1;
-        }
-        count
-    })
+pub fn solve_day6_part1(orbits: &OrbitTree) -> usize {
+    options::write(6, "DOT", || orbits.to_dot());
+    orbits.total_orbits()
 }
 
 #[aoc(day6, part2)]
-pub fn solve_day6_part2(orbits: &HashMap<String, String>) -> usize {
-    let get_path_to_root = |start: &str| {
-        let mut path = HashSet::new();
-        let mut current = start;
-        while let Some(orbited) = orbits.get(current) {
-            path.insert(orbited);
-            current = orbited;
-        }
-        path
+pub fn solve_day6_part2(orbits: &OrbitTree) -> usize {
+    let body = |name| {
+        orbits
+            .id(name)
+            .unwrap_or_else(|| panic!("ERROR: {} is not on the map", name))
     };
-    let you_path = get_path_to_root("YOU");
-    let san_path = get_path_to_root("SAN");
-    you_path.symmetric_difference(&san_path).count()
+    orbits
+        .transfers(body("YOU"), body("SAN"))
+        .expect("ERROR: YOU and SAN don't orbit anything in common")
 }
@@ -1,56 +1,54 @@
+use crate::grid::Grid;
+use crate::ocr;
+use crate::options;
+use crate::sif::{self, SifImage};
 use aoc_runner_derive::{aoc, aoc_generator};
 
 #[aoc_generator(day8)]
-pub fn parse_day8(input: &str) -> Vec<Vec<u32>> {
-    input
-        .as_bytes()
-        .chunks(25 * 6)
-        .map(|layer| {
-            std::str::from_utf8(layer)
-                .unwrap()
-                .chars()
-                .map(|x| x.to_digit(10).unwrap())
-                .collect()
-        })
-        .collect()
+pub fn parse_day8(input: &str) -> SifImage {
+    SifImage::parse(input, 25, 6).unwrap_or_else(|err| panic!("ERROR: invalid image: {}", err))
 }
 
 #[aoc(day8, part1)]
-pub fn solve_day8_part1(raw_image: &[Vec<u32>]) -> usize {
-    let count_digits = |layer: &[u32], digit| layer.iter().filter(|x| **x == digit).count();
-    raw_image
+pub fn solve_day8_part1(image: &SifImage) -> usize {
+    image
+        .stats()
         .iter()
-        .min_by_key(|layer| count_digits(layer, 0))
-        .map(|layer| count_digits(layer, 1) * count_digits(layer, 2))
+        .min_by_key(|layer| layer.count(0))
+        .map(|layer| layer.count(1) * layer.count(2))
         .unwrap()
 }
 
+// Black as a block and white as a space. A pixel no layer gives a color is shown as '?', and any
+// other color as its digit.
+fn render_image(image: &Grid<u8>) -> String {
+    image.render(|_, &pixel| match pixel {
+        sif::BLACK => '█',
+        sif::WHITE => ' ',
+        sif::TRANSPARENT => '?',
+        other => (b'0' + other) as char,
+    })
+}
+
 #[aoc(day8, part2)]
-pub fn solve_day8_part2(raw_image: &[Vec<u32>]) -> String {
-    raw_image
-        .iter()
-        .rev()
-        .fold(vec![2; 25 * 6], |mut acc, layer| {
-            for (i, pixel) in layer.iter().enumerate() {
-                if *pixel != 2 {
-                    acc[i] = *pixel
-                }
-            }
-            acc
-        })
-        .chunks(25)
-        .map(|row| {
-            row.iter()
-                .map(|x| match x {
-                    0 => '█',
-                    1 => ' ',
-                    _ => unreachable!(),
-                })
-                .collect::<String>()
-        })
-        .fold("\n".to_owned(), |mut acc, row| {
-            acc.push_str(&row);
-            acc.push('\n');
-            acc
-        })
+pub fn solve_day8_part2(image: &SifImage) -> String {
+    options::write(8, "PGM", || image.to_pgm());
+    let image = image.composite();
+    options::print(8, "IMAGE", || render_image(&image));
+    // The message is written in white
+    ocr::read(&image.map(|&pixel| pixel == sif::WHITE))
+        .unwrap_or_else(|error| panic!("ERROR: failed to read the message\n{}", error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_incomplete_image() {
+        let image = SifImage::parse("022211222212", 2, 2).unwrap();
+        assert_eq!(render_image(&image.composite()), "█ \n ?\n");
+        let image = SifImage::parse("2230", 2, 2).unwrap();
+        assert_eq!(render_image(&image.composite()), "??\n3█\n");
+    }
 }
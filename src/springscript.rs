@@ -0,0 +1,997 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+// The springdroid can only hold this many instructions
+pub const MAX_INSTRUCTIONS: usize = 15;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Register {
+    A,
+    B,
+    C,
+    D,
+    E,
+    F,
+    G,
+    H,
+    I,
+    T,
+    J,
+}
+
+impl Register {
+    fn from_char(ch: char) -> Option<Self> {
+        Some(match ch {
+            'A' => Register::A,
+            'B' => Register::B,
+            'C' => Register::C,
+            'D' => Register::D,
+            'E' => Register::E,
+            'F' => Register::F,
+            'G' => Register::G,
+            'H' => Register::H,
+            'I' => Register::I,
+            'T' => Register::T,
+            'J' => Register::J,
+            _ => return None,
+        })
+    }
+
+    fn symbol(self) -> char {
+        match self {
+            Register::A => 'A',
+            Register::B => 'B',
+            Register::C => 'C',
+            Register::D => 'D',
+            Register::E => 'E',
+            Register::F => 'F',
+            Register::G => 'G',
+            Register::H => 'H',
+            Register::I => 'I',
+            Register::T => 'T',
+            Register::J => 'J',
+        }
+    }
+
+    // How many tiles ahead a sensor looks, None for the temporary and jump registers
+    pub fn distance(self) -> Option<usize> {
+        match self {
+            Register::T | Register::J => None,
+            sensor => Some((sensor.symbol() as u8 - b'A') as usize + 1),
+        }
+    }
+
+    pub fn is_writable(self) -> bool {
+        self.distance().is_none()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Op {
+    And,
+    Or,
+    Not,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Instruction {
+    pub op: Op,
+    pub x: Register,
+    pub y: Register,
+    // Where the instruction was in the source, None if it wasn't parsed
+    pub location: Option<Location>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Location {
+    pub line: usize,
+    // The columns the instruction and its first register start at
+    pub column: usize,
+    pub x_column: usize,
+}
+
+impl Instruction {
+    // Where the instruction was parsed from, or where it would be as line `index` + 1 of the
+    // script formatted with Display
+    fn location(&self, index: usize) -> Location {
+        self.location.unwrap_or_else(|| Location {
+            line: index + 1,
+            column: 1,
+            x_column: self.to_string().find(' ').unwrap() + 2,
+        })
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let op = match self.op {
+            Op::And => "AND",
+            Op::Or => "OR",
+            Op::Not => "NOT",
+        };
+        write!(f, "{} {} {}", op, self.x.symbol(), self.y.symbol())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    Walk,
+    Run,
+}
+
+impl Mode {
+    // WALK can only see sensors A through D, RUN gets E through I as well
+    pub fn max_distance(self) -> usize {
+        match self {
+            Mode::Walk => 4,
+            Mode::Run => 9,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Error {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+fn error<T>(line: usize, column: usize, message: String) -> Result<T, Error> {
+    Err(Error {
+        line,
+        column,
+        message,
+    })
+}
+
+// Sensor readings are a bitmask with bit 0 being A, set if there is ground there
+fn sensor(sensors: u16, register: Register) -> bool {
+    sensors & 1 << (register.distance().unwrap() - 1) != 0
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Script {
+    pub instructions: Vec<Instruction>,
+    pub mode: Mode,
+}
+
+impl Script {
+    pub fn parse(source: &str) -> Result<Self, Error> {
+        let mut instructions = Vec::new();
+        let mut mode = None;
+        for (line_idx, line) in source.lines().enumerate() {
+            let line_no = line_idx + 1;
+            // Split into words, keeping the column each one starts at
+            let mut words = Vec::new();
+            let mut start = None;
+            for (i, ch) in line
+                .char_indices()
+                .chain(std::iter::once((line.len(), ' ')))
+            {
+                match (ch.is_whitespace(), start) {
+                    (true, Some(s)) => {
+                        words.push((s + 1, &line[s..i]));
+                        start = None;
+                    }
+                    (false, None) => start = Some(i),
+                    _ => (),
+                }
+            }
+            let (column, word) = match words.first() {
+                Some(first) => *first,
+                None => continue,
+            };
+            if mode.is_some() {
+                return error(line_no, column, "instruction after WALK/RUN".to_owned());
+            }
+            let op = match word {
+                "AND" => Op::And,
+                "OR" => Op::Or,
+                "NOT" => Op::Not,
+                "WALK" | "RUN" => {
+                    if let Some((column, _)) = words.get(1) {
+                        return error(line_no, *column, format!("{} takes no arguments", word));
+                    }
+                    mode = Some(if word == "WALK" {
+                        Mode::Walk
+                    } else {
+                        Mode::Run
+                    });
+                    continue;
+                }
+                _ => return error(line_no, column, format!("unknown instruction '{}'", word)),
+            };
+            if words.len() != 3 {
+                let column = words.get(3).map_or(line.len() + 1, |(column, _)| *column);
+                return error(line_no, column, format!("{} takes two registers", word));
+            }
+            let mut registers = Vec::new();
+            for (column, word) in words[1..].iter() {
+                let mut chars = word.chars();
+                match (chars.next().and_then(Register::from_char), chars.next()) {
+                    (Some(register), None) => registers.push(register),
+                    _ => return error(line_no, *column, format!("unknown register '{}'", word)),
+                }
+            }
+            if !registers[1].is_writable() {
+                return error(
+                    line_no,
+                    words[2].0,
+                    format!("cannot write to sensor {}", words[2].1),
+                );
+            }
+            instructions.push(Instruction {
+                op,
+                x: registers[0],
+                y: registers[1],
+                location: Some(Location {
+                    line: line_no,
+                    column,
+                    x_column: words[1].0,
+                }),
+            });
+        }
+        let mode = match mode {
+            Some(mode) => mode,
+            None => {
+                let lines = source.lines().count();
+                return error(lines.max(1), 1, "missing WALK or RUN".to_owned());
+            }
+        };
+        let script = Script { instructions, mode };
+        script.validate()?;
+        Ok(script)
+    }
+
+    // Check the script fits in the springdroid's memory and only reads sensors its mode has.
+    // Errors point at the offending instruction, see `Instruction::location`.
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.instructions.len() > MAX_INSTRUCTIONS {
+            let location = self.instructions[MAX_INSTRUCTIONS].location(MAX_INSTRUCTIONS);
+            return error(
+                location.line,
+                location.column,
+                format!(
+                    "{} instructions is more than the limit of {}",
+                    self.instructions.len(),
+                    MAX_INSTRUCTIONS
+                ),
+            );
+        }
+        for (i, instruction) in self.instructions.iter().enumerate() {
+            if let Some(distance) = instruction.x.distance() {
+                if distance > self.mode.max_distance() {
+                    let mode = if self.mode == Mode::Walk {
+                        "WALK"
+                    } else {
+                        "RUN"
+                    };
+                    let location = instruction.location(i);
+                    return error(
+                        location.line,
+                        location.x_column,
+                        format!(
+                            "sensor {} is not available with {}",
+                            instruction.x.symbol(),
+                            mode
+                        ),
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Whether the springdroid jumps given the current sensor readings
+    pub fn jumps(&self, sensors: u16) -> bool {
+        let mut t = false;
+        let mut j = false;
+        for instruction in self.instructions.iter() {
+            let x = match instruction.x {
+                Register::T => t,
+                Register::J => j,
+                register => sensor(sensors, register),
+            };
+            let y = if instruction.y == Register::T {
+                &mut t
+            } else {
+                &mut j
+            };
+            *y = match instruction.op {
+                Op::And => x && *y,
+                Op::Or => x || *y,
+                Op::Not => !x,
+            };
+        }
+        j
+    }
+}
+
+impl fmt::Display for Script {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for instruction in self.instructions.iter() {
+            writeln!(f, "{}", instruction)?;
+        }
+        match self.mode {
+            Mode::Walk => writeln!(f, "WALK"),
+            Mode::Run => writeln!(f, "RUN"),
+        }
+    }
+}
+
+// A boolean expression over the sensors such as `!(A & B & C) & D & (E | H)`
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    Sensor(Register),
+    Not(Box<Expr>),
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+}
+
+// Recursive descent over: or := and ('|' and)*, and := not ('&' not)*,
+// not := '!' not | '(' or ')' | sensor
+struct ExprParser<'a> {
+    len: usize,
+    chars: std::iter::Peekable<std::str::CharIndices<'a>>,
+}
+
+impl<'a> ExprParser<'a> {
+    fn peek(&mut self) -> Option<(usize, char)> {
+        while let Some((_, ch)) = self.chars.peek() {
+            if !ch.is_whitespace() {
+                break;
+            }
+            self.chars.next();
+        }
+        self.chars.peek().copied()
+    }
+
+    fn column(&mut self) -> usize {
+        self.peek().map_or(self.len, |(i, _)| i) + 1
+    }
+
+    fn parse_binary(&mut self, op: char) -> Result<Expr, Error> {
+        let mut operands = vec![if op == '|' {
+            self.parse_binary('&')?
+        } else {
+            self.parse_not()?
+        }];
+        while let Some((_, ch)) = self.peek() {
+            if ch != op {
+                break;
+            }
+            self.chars.next();
+            operands.push(if op == '|' {
+                self.parse_binary('&')?
+            } else {
+                self.parse_not()?
+            });
+        }
+        // Flatten so that A & (B & C) is the same as A & B & C
+        let operands = operands
+            .into_iter()
+            .flat_map(|operand| match (operand, op) {
+                (Expr::And(inner), '&') | (Expr::Or(inner), '|') => inner,
+                (operand, _) => vec![operand],
+            })
+            .collect::<Vec<_>>();
+        Ok(match (operands.len(), op) {
+            (1, _) => operands.into_iter().next().unwrap(),
+            (_, '&') => Expr::And(operands),
+            _ => Expr::Or(operands),
+        })
+    }
+
+    fn parse_not(&mut self) -> Result<Expr, Error> {
+        let column = self.column();
+        match self.peek() {
+            Some((_, '!')) => {
+                self.chars.next();
+                Ok(match self.parse_not()? {
+                    Expr::Not(inner) => *inner,
+                    operand => Expr::Not(Box::new(operand)),
+                })
+            }
+            Some((_, '(')) => {
+                self.chars.next();
+                let inner = self.parse_binary('|')?;
+                match self.peek() {
+                    Some((_, ')')) => {
+                        self.chars.next();
+                        Ok(inner)
+                    }
+                    _ => error(1, self.column(), "expected ')'".to_owned()),
+                }
+            }
+            Some((_, ch)) => match Register::from_char(ch) {
+                Some(register) if !register.is_writable() => {
+                    self.chars.next();
+                    Ok(Expr::Sensor(register))
+                }
+                _ => error(1, column, format!("expected a sensor, found '{}'", ch)),
+            },
+            None => error(1, column, "unexpected end of expression".to_owned()),
+        }
+    }
+}
+
+impl Expr {
+    pub fn parse(source: &str) -> Result<Self, Error> {
+        let mut parser = ExprParser {
+            len: source.len(),
+            chars: source.char_indices().peekable(),
+        };
+        let expr = parser.parse_binary('|')?;
+        match parser.peek() {
+            None => Ok(expr),
+            Some((i, ch)) => error(1, i + 1, format!("unexpected '{}'", ch)),
+        }
+    }
+
+    pub fn eval(&self, sensors: u16) -> bool {
+        match self {
+            Expr::Sensor(register) => sensor(sensors, *register),
+            Expr::Not(inner) => !inner.eval(sensors),
+            Expr::And(operands) => operands.iter().all(|operand| operand.eval(sensors)),
+            Expr::Or(operands) => operands.iter().any(|operand| operand.eval(sensors)),
+        }
+    }
+
+    fn max_distance(&self) -> usize {
+        match self {
+            Expr::Sensor(register) => register.distance().unwrap(),
+            Expr::Not(inner) => inner.max_distance(),
+            Expr::And(operands) | Expr::Or(operands) => {
+                operands.iter().map(Expr::max_distance).max().unwrap()
+            }
+        }
+    }
+}
+
+fn instruction(op: Op, x: Register, y: Register) -> Instruction {
+    Instruction {
+        op,
+        x,
+        y,
+        location: None,
+    }
+}
+
+// The shortest code found that leaves `expr` (or its negation) in `acc`. `acc_false` says whether
+// acc is known to be false beforehand, and `tmp` is a second register that may be used as scratch
+// space, along with whether it is known to be false.
+fn generate(
+    expr: &Expr,
+    negate: bool,
+    acc: Register,
+    acc_false: bool,
+    tmp: Option<(Register, bool)>,
+) -> Option<Vec<Instruction>> {
+    match expr {
+        Expr::Sensor(x) => Some(match (negate, acc_false) {
+            (true, _) => vec![instruction(Op::Not, *x, acc)],
+            (false, true) => vec![instruction(Op::Or, *x, acc)],
+            (false, false) => vec![
+                instruction(Op::Not, *x, acc),
+                instruction(Op::Not, acc, acc),
+            ],
+        }),
+        Expr::Not(inner) => generate(inner, !negate, acc, acc_false, tmp),
+        Expr::And(operands) | Expr::Or(operands) => {
+            let op = if let Expr::And(_) = expr {
+                Op::And
+            } else {
+                Op::Or
+            };
+            // Either compute it directly or use De Morgan's laws to compute its negation, and flip
+            // the result at the end if that's the wrong way round
+            let flipped = if op == Op::And { Op::Or } else { Op::And };
+            let direct = generate_operands(op, operands, false, acc, acc_false, tmp);
+            let de_morgan = generate_operands(flipped, operands, true, acc, acc_false, tmp);
+            let (right, wrong) = if negate {
+                (de_morgan, direct)
+            } else {
+                (direct, de_morgan)
+            };
+            let wrong = wrong.map(|mut code| {
+                code.push(instruction(Op::Not, acc, acc));
+                code
+            });
+            right.into_iter().chain(wrong).min_by_key(|code| code.len())
+        }
+    }
+}
+
+fn generate_operands(
+    op: Op,
+    operands: &[Expr],
+    negate: bool,
+    acc: Register,
+    acc_false: bool,
+    tmp: Option<(Register, bool)>,
+) -> Option<Vec<Instruction>> {
+    // Sensors can be combined into acc directly, anything else has to be built in tmp first
+    let direct = |operand: &Expr| match operand {
+        Expr::Sensor(x) if !negate => Some(*x),
+        _ => None,
+    };
+    (0..operands.len())
+        .filter_map(|first| {
+            let mut code = generate(&operands[first], negate, acc, acc_false, tmp)?;
+            let mut tmp = tmp.map(|(t, t_false)| {
+                (
+                    t,
+                    t_false && code.iter().all(|instruction| instruction.y != t),
+                )
+            });
+            for (_, operand) in operands.iter().enumerate().filter(|(i, _)| *i != first) {
+                if let Some(x) = direct(operand) {
+                    code.push(instruction(op, x, acc));
+                    continue;
+                }
+                let (t, t_false) = tmp?;
+                code.extend(generate(operand, negate, t, t_false, None)?);
+                code.push(instruction(op, t, acc));
+                tmp = Some((t, false));
+            }
+            Some(code)
+        })
+        .min_by_key(|code| code.len())
+}
+
+// Compile a boolean expression over the sensors into a script that jumps exactly when it is true.
+// The result is the shortest of the code shapes tried, which are accumulating into J with T used
+// for subexpressions, with or without De Morgan's laws applied at each level.
+// Scripts longer than MAX_INSTRUCTIONS are returned as they are, so check with `validate` before
+// using one.
+pub fn compile(source: &str, mode: Mode) -> Result<Script, Error> {
+    let expr = Expr::parse(source)?;
+    if expr.max_distance() > mode.max_distance() {
+        let column = source
+            .find(|ch: char| {
+                Register::from_char(ch)
+                    .and_then(Register::distance)
+                    .is_some_and(|distance| distance > mode.max_distance())
+            })
+            .unwrap();
+        return error(
+            1,
+            column + 1,
+            format!("sensor {} is not available", &source[column..=column]),
+        );
+    }
+    compile_expr(&expr, mode).ok_or_else(|| Error {
+        line: 1,
+        column: 1,
+        message: "expression needs more than two registers".to_owned(),
+    })
+}
+
+fn compile_expr(expr: &Expr, mode: Mode) -> Option<Script> {
+    let instructions = generate(expr, false, Register::J, true, Some((Register::T, true)))?;
+    let script = Script { instructions, mode };
+    debug_assert!((0..1 << mode.max_distance()).all(|s| script.jumps(s) == expr.eval(s)));
+    Some(script)
+}
+
+// Jumps carry the springdroid this many tiles forward
+const JUMP_DISTANCE: usize = 4;
+
+// Sensor readings at `position` on a hull such as "#####.#..###", where anything past the end is
+// ground
+fn sensors_at(hull: &[u8], position: usize, mode: Mode) -> u16 {
+    (1..=mode.max_distance())
+        .filter(|distance| hull.get(position + distance) != Some(&b'.'))
+        .fold(0, |sensors, distance| sensors | 1 << (distance - 1))
+}
+
+enum Crossing {
+    Crossed,
+    // The position of the hole the springdroid fell into
+    Fell(usize),
+    // The sensor readings it didn't know what to do with
+    Undecided(u16),
+}
+
+// Move along the hull from the first tile, asking `jumps` whether to jump from each position
+fn cross_hull(hull: &[u8], mode: Mode, mut jumps: impl FnMut(u16) -> Option<bool>) -> Crossing {
+    let mut position = 0;
+    while position < hull.len() {
+        let sensors = sensors_at(hull, position, mode);
+        position += match jumps(sensors) {
+            Some(true) => JUMP_DISTANCE,
+            Some(false) => 1,
+            None => return Crossing::Undecided(sensors),
+        };
+        if hull.get(position) == Some(&b'.') {
+            return Crossing::Fell(position);
+        }
+    }
+    Crossing::Crossed
+}
+
+// Run a script against a hull without the Intcode springdroid, returning the position of the hole
+// it falls into if it does
+pub fn simulate(script: &Script, hull: &str) -> Result<(), usize> {
+    match cross_hull(hull.as_bytes(), script.mode, |sensors| {
+        Some(script.jumps(sensors))
+    }) {
+        Crossing::Crossed => Ok(()),
+        Crossing::Fell(position) => Err(position),
+        Crossing::Undecided(_) => unreachable!(),
+    }
+}
+
+// The most ways of deciding when to jump that are looked for. Every hull can double how many
+// there are, so past this the shortest script is picked from the ones already found.
+const MAX_DECISION_SETS: usize = 1024;
+
+// Find every way of deciding when to jump that gets the springdroid across all of the hulls, up to
+// MAX_DECISION_SETS of them. Only the sensor readings it actually comes across are decided.
+fn find_decisions(
+    hulls: &[&[u8]],
+    mode: Mode,
+    decisions: &mut BTreeMap<u16, bool>,
+    found: &mut Vec<BTreeMap<u16, bool>>,
+) {
+    if found.len() == MAX_DECISION_SETS {
+        return;
+    }
+    for hull in hulls {
+        match cross_hull(hull, mode, |sensors| decisions.get(&sensors).copied()) {
+            Crossing::Crossed => (),
+            Crossing::Fell(_) => return,
+            Crossing::Undecided(sensors) => {
+                for &jump in [false, true].iter() {
+                    decisions.insert(sensors, jump);
+                    find_decisions(hulls, mode, decisions, found);
+                }
+                decisions.remove(&sensors);
+                return;
+            }
+        }
+    }
+    found.push(decisions.clone());
+}
+
+// A product of sensors such as !A & D, stored as a mask of the sensors used and the values they
+// need to have
+type Term = (u16, u16);
+
+fn covers(term: Term, sensors: u16) -> bool {
+    sensors & term.0 == term.1
+}
+
+// The smallest set of products, counting both sensors and products, that between them cover every
+// reading in `on` and none in `off`. Readings in neither are don't cares.
+fn minimal_cover(on: &[u16], off: &[u16], mode: Mode) -> Vec<Term> {
+    let sensors = mode.max_distance();
+    let implicant = |term: Term| !off.iter().any(|&reading| covers(term, reading));
+    let mut primes = Vec::new();
+    for mask in 0..1u16 << sensors {
+        let mut value = mask;
+        loop {
+            let term = (mask, value);
+            let prime = implicant(term)
+                && on.iter().any(|&reading| covers(term, reading))
+                && (0..sensors)
+                    .filter(|i| mask & 1 << i != 0)
+                    .all(|i| !implicant((mask & !(1 << i), value & !(1 << i))));
+            if prime {
+                primes.push(term);
+            }
+            if value == 0 {
+                break;
+            }
+            value = (value - 1) & mask;
+        }
+    }
+
+    fn search(
+        on: &[u16],
+        primes: &[Term],
+        chosen: &mut Vec<Term>,
+        cost: u32,
+        best: &mut Option<(u32, Vec<Term>)>,
+    ) {
+        if best.as_ref().is_some_and(|(best, _)| cost >= *best) {
+            return;
+        }
+        let uncovered = on
+            .iter()
+            .find(|&&reading| !chosen.iter().any(|&term| covers(term, reading)));
+        match uncovered {
+            None => *best = Some((cost, chosen.clone())),
+            Some(&reading) => {
+                for &term in primes.iter().filter(|&&term| covers(term, reading)) {
+                    chosen.push(term);
+                    search(on, primes, chosen, cost + term.0.count_ones() + 1, best);
+                    chosen.pop();
+                }
+            }
+        }
+    }
+    let mut best = None;
+    search(on, &primes, &mut Vec::new(), 0, &mut best);
+    best.unwrap().1
+}
+
+// The sum of the products, or None if that is always false
+fn sum_of_products(terms: &[Term]) -> Option<Expr> {
+    const SENSORS: [Register; 9] = [
+        Register::A,
+        Register::B,
+        Register::C,
+        Register::D,
+        Register::E,
+        Register::F,
+        Register::G,
+        Register::H,
+        Register::I,
+    ];
+    let flatten = |mut operands: Vec<Expr>, combine: fn(Vec<Expr>) -> Expr| {
+        if operands.len() == 1 {
+            operands.pop().unwrap()
+        } else {
+            combine(operands)
+        }
+    };
+    let products = terms
+        .iter()
+        .map(|&(mask, value)| {
+            let literals = (0..SENSORS.len())
+                .filter(|i| mask & 1 << i != 0)
+                .map(|i| match value & 1 << i {
+                    0 => Expr::Not(Box::new(Expr::Sensor(SENSORS[i]))),
+                    _ => Expr::Sensor(SENSORS[i]),
+                })
+                .collect();
+            flatten(literals, Expr::And)
+        })
+        .collect::<Vec<_>>();
+    if products.is_empty() {
+        None
+    } else {
+        Some(flatten(products, Expr::Or))
+    }
+}
+
+// A script that makes the given decisions, the shorter of the compiled minimal sum of products and
+// product of sums matching them
+fn cover_script(decisions: &BTreeMap<u16, bool>, mode: Mode) -> Option<Script> {
+    let (on, off): (Vec<_>, Vec<_>) = decisions.iter().partition(|(_, &jump)| jump);
+    let on = on
+        .into_iter()
+        .map(|(&reading, _)| reading)
+        .collect::<Vec<_>>();
+    let off = off
+        .into_iter()
+        .map(|(&reading, _)| reading)
+        .collect::<Vec<_>>();
+    let never = Script {
+        instructions: Vec::new(),
+        mode,
+    };
+    let always = Script {
+        instructions: vec![instruction(Op::Not, Register::T, Register::J)],
+        mode,
+    };
+    let products = minimal_cover(&on, &off, mode);
+    let sums = minimal_cover(&off, &on, mode);
+    // The empty product is always true, so covering with it means always or never jumping
+    let candidates = vec![
+        match sum_of_products(&products) {
+            _ if products.contains(&(0, 0)) => Some(always.clone()),
+            None => Some(never.clone()),
+            Some(expr) => compile_expr(&expr, mode),
+        },
+        match sum_of_products(&sums) {
+            _ if sums.contains(&(0, 0)) => Some(never),
+            None => Some(always),
+            Some(expr) => compile_expr(&Expr::Not(Box::new(expr)), mode),
+        },
+    ];
+    candidates
+        .into_iter()
+        .flatten()
+        .min_by_key(|script| script.instructions.len())
+}
+
+// The shortest script found that crosses all of the hulls. For each way of deciding when to jump
+// that works, the minimal sum of products and product of sums matching those decisions are compiled
+// and the shortest result is kept. Scripts aren't enumerated, so a factored expression such as
+// A & (B | C) can still be shorter than the result.
+fn shortest_script(mode: Mode, hulls: &[String]) -> Result<Script, SynthesisError> {
+    let hulls = hulls.iter().map(|hull| hull.as_bytes()).collect::<Vec<_>>();
+    let mut found = Vec::new();
+    find_decisions(&hulls, mode, &mut BTreeMap::new(), &mut found);
+    let best = found
+        .iter()
+        .filter_map(|decisions| cover_script(decisions, mode))
+        .min_by_key(|script| script.instructions.len())
+        .ok_or(SynthesisError::Impossible)?;
+    match best.validate() {
+        Ok(()) => Ok(best),
+        Err(error) => Err(SynthesisError::TooLong(best, error)),
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SynthesisError {
+    // No way of deciding when to jump gets the springdroid across every hull
+    Impossible,
+    // The shortest script found, which doesn't fit in the springdroid, and why
+    TooLong(Script, Error),
+    // The springdroid fell on a hull that the simulator says the script crosses
+    Disagreement(Script, String),
+}
+
+impl fmt::Display for SynthesisError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SynthesisError::Impossible => write!(f, "no script can cross every hull"),
+            SynthesisError::TooLong(script, error) => {
+                write!(
+                    f,
+                    "the shortest script found is too long, {}\n{}",
+                    error, script
+                )
+            }
+            SynthesisError::Disagreement(script, hull) => write!(
+                f,
+                "the springdroid fell on {} but the simulator crosses it with\n{}",
+                hull, script
+            ),
+        }
+    }
+}
+
+// Counterexample guided synthesis of a script. `check` runs a candidate on the real springdroid and
+// returns the hull it fell on, if it did. Each candidate is the shortest script found that crosses
+// every hull reported so far, which isn't necessarily the shortest one there is, see
+// `shortest_script`.
+pub fn synthesize(
+    mode: Mode,
+    mut check: impl FnMut(&Script) -> Option<String>,
+) -> Result<Script, SynthesisError> {
+    let mut hulls = Vec::new();
+    loop {
+        let script = shortest_script(mode, &hulls)?;
+        match check(&script) {
+            None => return Ok(script),
+            // The simulator disagrees with the springdroid, so there is no point carrying on
+            Some(hull) if simulate(&script, &hull).is_ok() => {
+                return Err(SynthesisError::Disagreement(script, hull))
+            }
+            Some(hull) => hulls.push(hull),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_format() {
+        let script = Script::parse("NOT A J\nOR  B   T \nAND T J\nWALK\n").unwrap();
+        assert_eq!(script.instructions.len(), 3);
+        assert_eq!(script.mode, Mode::Walk);
+        assert_eq!(script.to_string(), "NOT A J\nOR B T\nAND T J\nWALK\n");
+    }
+
+    #[test]
+    fn parse_errors() {
+        let message = |source: &str| Script::parse(source).unwrap_err().to_string();
+        assert_eq!(
+            message("NOT A J\nAND X J\nWALK"),
+            "2:5: unknown register 'X'"
+        );
+        assert_eq!(message("NOT J A\nWALK"), "1:7: cannot write to sensor A");
+        assert_eq!(message("XOR A J\nWALK"), "1:1: unknown instruction 'XOR'");
+        assert_eq!(message("NOT A\nWALK"), "1:6: NOT takes two registers");
+        assert_eq!(message("NOT A J"), "1:1: missing WALK or RUN");
+        assert_eq!(
+            message("NOT E J\nWALK"),
+            "1:5: sensor E is not available with WALK"
+        );
+        assert_eq!(
+            message(&format!("{}WALK", "NOT A J\n".repeat(16))),
+            "16:1: 16 instructions is more than the limit of 15"
+        );
+        // Blank lines and indentation still count
+        assert_eq!(
+            message("NOT A J\n\n  NOT  E J\nWALK"),
+            "3:8: sensor E is not available with WALK"
+        );
+        assert!(message(&format!("\n{}WALK", "  NOT A J\n".repeat(16))).starts_with("17:3:"));
+    }
+
+    #[test]
+    fn compile_day21() {
+        let script = compile("!(A & B & C) & D & (E | H)", Mode::Run).unwrap();
+        assert_eq!(
+            script.to_string(),
+            "OR A J\nAND B J\nAND C J\nNOT J J\nAND D J\nOR E T\nOR H T\nAND T J\nRUN\n"
+        );
+        assert_eq!(
+            compile("!A & D", Mode::Walk).unwrap().to_string(),
+            "NOT A J\nAND D J\nWALK\n"
+        );
+        assert_eq!(
+            compile("A & E", Mode::Walk).unwrap_err().to_string(),
+            "1:5: sensor E is not available"
+        );
+        assert_eq!(
+            compile("A & (B", Mode::Walk).unwrap_err().to_string(),
+            "1:7: expected ')'"
+        );
+    }
+
+    #[test]
+    fn simulate_hulls() {
+        let script = compile("!(A & B & C) & D", Mode::Walk).unwrap();
+        assert_eq!(simulate(&script, "#####.#..########"), Ok(()));
+        assert_eq!(simulate(&script, "#####.#.##.#.####"), Err(7));
+        let never = Script::parse("WALK").unwrap();
+        assert_eq!(simulate(&never, "#####.###########"), Err(5));
+    }
+
+    #[test]
+    fn synthesize_offline() {
+        let hulls = [
+            "#####.###########",
+            "#####...#########",
+            "#####..#.########",
+            "#####.#.##..#.###",
+            "#####.##.##...###",
+        ];
+        let mut checks = 0;
+        let script = synthesize(Mode::Run, |script| {
+            checks += 1;
+            hulls
+                .iter()
+                .find(|hull| simulate(script, hull).is_err())
+                .map(|hull| hull.to_string())
+        })
+        .unwrap();
+        assert!(checks <= hulls.len() + 1);
+        assert!(script.instructions.len() <= MAX_INSTRUCTIONS);
+        assert!(hulls.iter().all(|hull| simulate(&script, hull).is_ok()));
+    }
+
+    #[test]
+    fn synthesis_is_not_minimal() {
+        // Jumping exactly when A & (B | C) takes two products or two sums, while the factored
+        // form compiles to fewer instructions
+        let jumps = |sensors: u16| sensors & 1 != 0 && sensors & 0b110 != 0;
+        let decisions = (0..16).map(|sensors| (sensors, jumps(sensors))).collect();
+        let covered = cover_script(&decisions, Mode::Walk).unwrap();
+        let factored = compile("A & (B | C)", Mode::Walk).unwrap();
+        assert!((0..16).all(|sensors| covered.jumps(sensors) == jumps(sensors)));
+        assert!((0..16).all(|sensors| factored.jumps(sensors) == jumps(sensors)));
+        assert_eq!(covered.instructions.len(), 5);
+        assert_eq!(factored.instructions.len(), 3);
+    }
+
+    #[test]
+    fn synthesize_errors() {
+        // A hole four tiles wide can't be jumped
+        let impossible = synthesize(Mode::Walk, |_| Some("#....####".to_owned()));
+        assert_eq!(impossible, Err(SynthesisError::Impossible));
+        // Never jumping crosses a hull with no holes, whatever the springdroid says
+        match synthesize(Mode::Walk, |_| Some("#########".to_owned())) {
+            Err(SynthesisError::Disagreement(script, hull)) => {
+                assert_eq!(script.to_string(), "WALK\n");
+                assert_eq!(hull, "#########");
+            }
+            other => panic!("expected a disagreement, got {:?}", other),
+        }
+    }
+}